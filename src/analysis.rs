@@ -0,0 +1,394 @@
+use std::collections::HashSet;
+use std::ops::Range;
+use regex::{Captures, Regex};
+
+lazy_static! {
+    static ref HEADING: Regex = Regex::new(r"(?is)<h([2-4])([^>]*)>(.*?)</h([2-4])>").unwrap();
+    static ref CLOSING_HEADING: Regex = Regex::new(r"(?i)</h([2-4])>").unwrap();
+    static ref ID: Regex = Regex::new(r#"(?i)(?:^|\s)id\s*=\s*"([^"]*)""#).unwrap();
+    static ref MORE: Regex = Regex::new(r"(?i)<!--\s*more\s*-->").unwrap();
+    static ref PARAGRAPH: Regex = Regex::new(r"(?is)<p[\s>].*?</p>").unwrap();
+    static ref TAG: Regex = Regex::new(r#"<(/?)([a-zA-Z][a-zA-Z0-9]*)(?:[^>"']|"[^"]*"|'[^']*')*?(/?)>"#).unwrap();
+    static ref ENTITY: Regex = Regex::new(r"&(?:#([0-9]{1,7})|#[xX]([0-9a-fA-F]{1,6})|([a-zA-Z]+));").unwrap();
+}
+
+/// Reading speed used for estimating reading times
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Elements whose closing tag separates words, unlike inline elements such as `<em>`
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p", "div", "br", "h1", "h2", "h3", "h4", "h5", "h6", "li", "pre", "tr", "td", "th", "blockquote",
+];
+
+/// Elements which never have a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+/// A heading within an article, along with the headings nested below it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Heading {
+    pub level: u32,
+    pub id: String,
+    /// Text of the heading with any markup removed, but still HTML-escaped
+    pub title: String,
+    pub children: Vec<Heading>,
+}
+
+/// A heading within the body, running up to the closing tag of the same level
+struct HeadingMatch<'a> {
+    range: Range<usize>,
+    level: &'a str,
+    attributes: &'a str,
+    content: &'a str,
+}
+
+/// Finds every h2 through h4 in the body. The regex crate can't tie the closing tag to the
+/// opening one, so when the first closing tag is of another level, such as that of a heading
+/// nested within, the search carries on to the next one of the same level.
+fn headings(body: &str) -> Vec<HeadingMatch<'_>> {
+    let mut headings = Vec::new();
+    let mut position = 0;
+
+    while let Some(captures) = HEADING.captures(&body[position..]) {
+        let (whole, content) = (captures.get(0).unwrap(), captures.get(3).unwrap());
+        let content_start = position + content.start();
+
+        let closing = match captures[4] == captures[1] {
+            true => Some((content.end(), whole.end())),
+            false => CLOSING_HEADING.captures_iter(&body[content_start..])
+                .find(|closing| closing[1] == captures[1])
+                .map(|closing| {
+                    let tag = closing.get(0).unwrap();
+                    (content.start() + tag.start(), content.start() + tag.end())
+                }),
+        };
+
+        match closing {
+            Some((content_end, end)) => {
+                headings.push(HeadingMatch {
+                    range: position + whole.start()..position + end,
+                    level: captures.get(1).unwrap().as_str(),
+                    attributes: captures.get(2).unwrap().as_str(),
+                    content: &body[content_start..position + content_end],
+                });
+                position += end;
+            }
+            // Never closed, so it isn't treated as a heading, though any heading within it still is
+            None => position = content_start,
+        }
+    }
+
+    headings
+}
+
+/// Facts about a rendered article body, computed once when the article is loaded
+pub struct Analysis {
+    /// The body with ids and self-links added to every heading
+    pub body: String,
+    pub words: usize,
+    /// Estimated reading time in minutes, at least one
+    pub reading_time: usize,
+    pub toc: Vec<Heading>,
+}
+
+/// Removes every tag from the HTML, along with the contents of style and script
+/// elements. Entities are left as they are, so the result is still safe to embed.
+pub fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut hidden = false;
+
+    while let Some(start) = rest.find('<') {
+        if !hidden {
+            text.push_str(&rest[..start]);
+        }
+
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        let tag = rest[start + 1..end].trim_start_matches('/');
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+
+        if name.eq_ignore_ascii_case("style") || name.eq_ignore_ascii_case("script") {
+            hidden = !rest[start + 1..].starts_with('/');
+        } else if BLOCK_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+            text.push(' ');
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    if !hidden {
+        text.push_str(rest);
+    }
+
+    text
+}
+
+/// Escapes text for embedding in HTML
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Character a named entity stands for, out of the ones likely to turn up in articles
+fn named_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201c}',
+        "rdquo" => '\u{201d}',
+        "hellip" => '\u{2026}',
+        "bull" => '\u{2022}',
+        "middot" => '\u{b7}',
+        "copy" => '\u{a9}',
+        "reg" => '\u{ae}',
+        "trade" => '\u{2122}',
+        "larr" => '\u{2190}',
+        "rarr" => '\u{2192}',
+        "times" => '\u{d7}',
+        _ => return None,
+    })
+}
+
+/// Plain text version of the HTML, for use outside of HTML documents. Numeric entities
+/// and the common named ones are decoded, while unknown ones are left as they are.
+pub fn plain_text(html: &str) -> String {
+    ENTITY.replace_all(&strip_tags(html), |captures: &Captures<'_>| {
+        let decoded = match (captures.get(1), captures.get(2), captures.get(3)) {
+            (Some(decimal), _, _) => decimal.as_str().parse().ok().and_then(std::char::from_u32),
+            (_, Some(hex), _) => u32::from_str_radix(hex.as_str(), 16).ok().and_then(std::char::from_u32),
+            (_, _, Some(name)) => named_entity(name.as_str()),
+            _ => None,
+        };
+
+        decoded.map(String::from).unwrap_or_else(|| captures[0].to_string())
+    }).into_owned()
+}
+
+/// Splits text into lowercase words, for indexing and comparing articles
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Closes every element left open in the HTML, such as when it has been cut short
+pub fn balance(html: &str) -> String {
+    let mut open: Vec<String> = Vec::new();
+
+    for tag in TAG.captures_iter(html) {
+        let name = tag[2].to_ascii_lowercase();
+
+        if !tag[1].is_empty() {
+            if let Some(position) = open.iter().rposition(|element| *element == name) {
+                open.truncate(position);
+            }
+        } else if tag[3].is_empty() && !VOID_ELEMENTS.contains(&name.as_str()) {
+            open.push(name);
+        }
+    }
+
+    let mut balanced = html.trim_end().to_string();
+    for element in open.iter().rev() {
+        balanced.push_str(&format!("</{}>", element));
+    }
+
+    balanced
+}
+
+/// The part of the body shown in listings. Everything before a `<!-- more -->` marker if
+/// there is one, otherwise the first paragraph, and failing that the body in its entirety.
+/// Either way the result is balanced, so it can't break the markup of the page around it.
+pub fn excerpt(body: &str) -> String {
+    if let Some(marker) = MORE.find(body) {
+        return balance(&body[..marker.start()]);
+    }
+
+    match PARAGRAPH.find(body) {
+        Some(paragraph) => paragraph.as_str().to_string(),
+        None => balance(body),
+    }
+}
+
+/// Turns the text of a heading into an id, keeping letters and digits and replacing
+/// everything between them with dashes, so "What's new in 2.0?" becomes "whats-new-in-2-0"
+fn anchor(text: &str) -> String {
+    let mut id = String::with_capacity(text.len());
+
+    for c in plain_text(text).chars() {
+        if c.is_alphanumeric() {
+            id.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_' || c == '.') && !id.ends_with('-') {
+            id.push('-');
+        }
+    }
+
+    match id.trim_matches('-') {
+        "" => "section".into(),
+        id => id.to_string(),
+    }
+}
+
+/// Places the heading below the last heading of a higher level, if there is one
+fn nest(headings: &mut Vec<Heading>, heading: Heading) {
+    match headings.last_mut() {
+        Some(last) if last.level < heading.level => nest(&mut last.children, heading),
+        _ => headings.push(heading),
+    }
+}
+
+/// Counts the words of the body and assigns an id to every h2 through h4, which
+/// are linked to from the table of contents and from the headings themselves.
+/// The self-links are left empty and given their text by the stylesheet, so
+/// they don't end up in feeds or anywhere else the body is used as text.
+/// Ids already present on headings are kept, and duplicate ids are numbered so
+/// that the same article always ends up with the same anchors.
+pub fn analyse(body: &str) -> Analysis {
+    let words = plain_text(body).split_whitespace().count();
+    let mut toc = Vec::new();
+
+    let headings = headings(body);
+
+    // Ids given explicitly are reserved up front, so generated ids steer clear
+    // of them even when the heading carrying them comes later in the article
+    let mut used: HashSet<String> = headings.iter()
+        .filter_map(|heading| ID.captures(heading.attributes).map(|existing| existing[1].to_string()))
+        .collect();
+
+    let mut analysed = String::with_capacity(body.len());
+    let mut position = 0;
+
+    for heading in &headings {
+        let HeadingMatch { level, attributes, content, .. } = *heading;
+
+        let id = match ID.captures(attributes) {
+            Some(existing) => existing[1].to_string(),
+            None => {
+                let base = anchor(content);
+                let mut id = base.clone();
+                let mut counter = 1;
+                while used.contains(&id) {
+                    id = format!("{}-{}", base, counter);
+                    counter += 1;
+                }
+                id
+            }
+        };
+        used.insert(id.clone());
+
+        nest(&mut toc, Heading {
+            level: level.parse().unwrap_or(2),
+            id: id.clone(),
+            title: strip_tags(content).trim().to_string(),
+            children: Vec::new(),
+        });
+
+        let attributes = match ID.is_match(attributes) {
+            true => attributes.to_string(),
+            false => format!(" id=\"{}\"{}", id, attributes),
+        };
+
+        analysed.push_str(&body[position..heading.range.start]);
+        analysed.push_str(&format!(
+            "<h{level}{attributes}>{content} <a class=\"anchor\" href=\"#{id}\" title=\"Link to this section\"></a></h{level}>",
+            level = level, attributes = attributes, content = content, id = id
+        ));
+        position = heading.range.end;
+    }
+    analysed.push_str(&body[position..]);
+
+    Analysis {
+        body: analysed,
+        words,
+        reading_time: std::cmp::max(1, words.div_ceil(WORDS_PER_MINUTE)),
+        toc,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_decodes_entities() {
+        assert_eq!(plain_text("<p>Fish &amp; chips&nbsp;&mdash; &#8217;n&#x27;</p>"), " Fish & chips\u{a0}\u{2014} \u{2019}n' ");
+        assert_eq!(plain_text("&amp;lt; &bogus; &#xFFFFFF;"), "&lt; &bogus; &#xFFFFFF;");
+    }
+
+    fn ids(toc: &[Heading]) -> Vec<String> {
+        toc.iter().flat_map(|heading| std::iter::once(heading.id.clone()).chain(ids(&heading.children))).collect()
+    }
+
+    #[test]
+    fn headings_are_closed_at_their_own_level() {
+        let analysis = analyse("<h2>a<h3>b</h3>c</h2><p>text</p><h3>d</h3>");
+
+        assert_eq!(ids(&analysis.toc), vec!["a-b-c", "d"]);
+        assert_eq!(analysis.toc[0].title, "a b c");
+        assert_eq!(analysis.body, "<h2 id=\"a-b-c\">a<h3>b</h3>c \
+            <a class=\"anchor\" href=\"#a-b-c\" title=\"Link to this section\"></a></h2><p>text</p>\
+            <h3 id=\"d\">d <a class=\"anchor\" href=\"#d\" title=\"Link to this section\"></a></h3>");
+    }
+
+    #[test]
+    fn mismatched_headings_are_skipped() {
+        let analysis = analyse("<h2>unclosed</h3><p>text</p><h4>last</h4>");
+
+        assert_eq!(ids(&analysis.toc), vec!["last"]);
+        assert!(analysis.body.starts_with("<h2>unclosed</h3><p>text</p><h4 id=\"last\">"));
+    }
+
+    #[test]
+    fn explicit_ids_are_kept_and_reserved() {
+        let analysis = analyse("<h2>Intro</h2><h2 class=\"x\" ID = \"intro\">Other</h2><h2 data-id=\"nope\">Data</h2>");
+
+        assert_eq!(ids(&analysis.toc), vec!["intro-1", "intro", "data"]);
+        assert!(analysis.body.contains("<h2 id=\"data\" data-id=\"nope\">Data "));
+    }
+
+    #[test]
+    fn elements_cut_short_are_closed() {
+        assert_eq!(balance("<div><p>Some <em>text <strong>cut"), "<div><p>Some <em>text <strong>cut</strong></em></p></div>");
+        assert_eq!(balance("<ul><li>one</li><li>two</li>"), "<ul><li>one</li><li>two</li></ul>");
+        assert_eq!(balance("<p>done</p>\n\n"), "<p>done</p>");
+    }
+
+    #[test]
+    fn void_and_self_closing_elements_stay_open() {
+        assert_eq!(balance("<p>a<br>b<img src=\"x.png\"/><hr/><span/>c"), "<p>a<br>b<img src=\"x.png\"/><hr/><span/>c</p>");
+        assert_eq!(balance("<P>Upper<BR>case"), "<P>Upper<BR>case</p>");
+    }
+
+    #[test]
+    fn quoted_attributes_may_contain_brackets() {
+        assert_eq!(balance("<p><a title=\"a > b\" href='/x'>link"), "<p><a title=\"a > b\" href='/x'>link</a></p>");
+        assert_eq!(balance("<p><a title=\"x > <b>\">link"), "<p><a title=\"x > <b>\">link</a></p>");
+    }
+
+    #[test]
+    fn excerpts_end_at_the_more_marker() {
+        let body = "<p>First</p>\n<blockquote><p>Quote <!-- More --> rest</p></blockquote><p>Second</p>";
+        assert_eq!(excerpt(body), "<p>First</p>\n<blockquote><p>Quote</p></blockquote>");
+        assert_eq!(excerpt("<p>First</p><p>Second <!--more--></p>"), "<p>First</p><p>Second</p>");
+    }
+
+    #[test]
+    fn excerpts_default_to_the_first_paragraph() {
+        assert_eq!(excerpt("<h2>Title</h2><p class=\"lead\">First</p><p>Second</p>"), "<p class=\"lead\">First</p>");
+        assert_eq!(excerpt("<pre>code"), "<pre>code</pre>");
+    }
+}
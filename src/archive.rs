@@ -0,0 +1,61 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use crate::model::Article;
+use crate::pagination::{PageLink, Pagination};
+
+/// Every listed article published within a single month
+#[derive(Serialize)]
+pub struct ArchiveMonth<'a> {
+    pub year: i32,
+    pub month: u32,
+    /// Timestamp of the first moment of the month, so templates can format it with `date`
+    pub start: i64,
+    pub articles: Vec<&'a Article>,
+}
+
+/// Groups articles by the month they were published in. Articles are expected to already
+/// be sorted newest first, which is also the order the months are returned in.
+pub fn by_month<'a>(articles: &[&'a Article]) -> Vec<ArchiveMonth<'a>> {
+    let mut months: Vec<ArchiveMonth<'a>> = Vec::new();
+
+    for &article in articles {
+        let published = NaiveDateTime::from_timestamp(article.published as i64, 0);
+        let (year, month) = (published.year(), published.month());
+
+        match months.last_mut() {
+            Some(last) if last.year == year && last.month == month => last.articles.push(article),
+            _ => months.push(ArchiveMonth {
+                year,
+                month,
+                start: NaiveDate::from_ymd(year, month, 1).and_hms(0, 0, 0).timestamp(),
+                articles: vec![article],
+            }),
+        }
+    }
+
+    months
+}
+
+/// Years which have at least one article, newest first
+pub fn years(months: &[ArchiveMonth]) -> Vec<i32> {
+    let mut years: Vec<i32> = months.iter().map(|month| month.year).collect();
+    years.dedup();
+    years
+}
+
+/// Links to the neighbours of `current` within `keys`, which are ordered newest first
+pub fn neighbours<K, U, L>(keys: &[K], current: &K, url: U, label: L) -> Option<Pagination>
+where
+    K: PartialEq,
+    U: Fn(&K) -> String,
+    L: Fn(&K) -> String,
+{
+    let position = keys.iter().position(|key| key == current)?;
+    let link = |key: &K| PageLink { url: url(key), label: label(key) };
+
+    Some(Pagination {
+        current: position + 1,
+        total: keys.len(),
+        previous: position.checked_sub(1).map(|previous| link(&keys[previous])),
+        next: keys.get(position + 1).map(link),
+    })
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::error::BlogError;
+use crate::highlighter;
+
+/// Site-wide settings, loaded from a TOML file alongside the rest of the resources.
+/// Every setting has a default, so the file only needs to contain the ones that differ.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub title: String,
+    pub description: String,
+    /// Absolute URL the blog is served from, without a trailing slash.
+    /// Used wherever relative links won't do, such as in feeds.
+    pub base_url: String,
+    /// Language of articles which don't specify one. Articles in this
+    /// language are served without a language prefix in their URLs.
+    pub language: String,
+    /// Names of every language articles are written in, keyed by their language code
+    pub languages: BTreeMap<String, String>,
+    /// Number of articles per page on the frontpage
+    pub page_size: usize,
+    /// Number of articles included in feeds
+    pub feed_size: usize,
+    /// Number of similar articles suggested below each article
+    pub related_articles: usize,
+    /// Crawler rules served in robots.txt
+    pub robots: Vec<CrawlerRule>,
+    /// Legacy paths, such as "/old/post.html", permanently redirected to their new location
+    pub redirects: BTreeMap<String, String>,
+    /// How code blocks are colored
+    pub highlight: Highlighting,
+}
+
+/// Coloring of highlighted code, either inline using a single theme, or by classes
+/// colored by style sheets, which lets readers preferring a dark color scheme get a dark theme
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Highlighting {
+    /// Whether code is marked up with classes instead of colored inline
+    pub classes: bool,
+    /// Theme coloring code, and with classes still used inline for code in feeds
+    pub theme: String,
+    /// Theme used instead when the reader prefers a dark color scheme. Requires classes.
+    pub dark_theme: Option<String>,
+}
+
+/// A group of robots.txt rules applying to a single user agent
+#[derive(Serialize, Deserialize)]
+pub struct CrawlerRule {
+    pub user_agent: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub disallow: Vec<String>,
+    pub crawl_delay: Option<u32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            title: "Blog".into(),
+            description: String::new(),
+            base_url: "http://localhost:8080".into(),
+            language: "en".into(),
+            languages: vec![("en".to_string(), "English".to_string())].into_iter().collect(),
+            page_size: 10,
+            feed_size: 20,
+            related_articles: 3,
+            robots: vec![CrawlerRule {
+                user_agent: "*".into(),
+                allow: Vec::new(),
+                disallow: Vec::new(),
+                crawl_delay: None,
+            }],
+            redirects: BTreeMap::new(),
+            highlight: Highlighting::default(),
+        }
+    }
+}
+
+impl Default for Highlighting {
+    fn default() -> Self {
+        Highlighting {
+            classes: false,
+            theme: highlighter::DEFAULT_THEME.into(),
+            dark_theme: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, BlogError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| BlogError::IOError(format!("{}: {}", path.display(), e)))?;
+
+        let mut config: Config = toml::from_str(&text).map_err(|e| {
+            BlogError::MetadataError(format!("{}: invalid configuration: {}", path.display(), e))
+        })?;
+
+        config.base_url = config.base_url.trim_end_matches('/').to_string();
+
+        if config.page_size == 0 {
+            return Err(BlogError::MetadataError(format!(
+                "{}: page_size must be at least 1", path.display()
+            )));
+        }
+
+        if !config.languages.contains_key(&config.language) {
+            return Err(BlogError::MetadataError(format!(
+                "{}: default language {:?} is missing from languages", path.display(), config.language
+            )));
+        }
+
+        let highlight = &config.highlight;
+        if let Some(theme) = std::iter::once(&highlight.theme).chain(&highlight.dark_theme).find(|theme| !highlighter::has_theme(theme)) {
+            return Err(BlogError::MetadataError(format!(
+                "{}: unknown highlighting theme {:?}, expected one of {}",
+                path.display(), theme, highlighter::themes().collect::<Vec<_>>().join(", ")
+            )));
+        }
+
+        if highlight.dark_theme.is_some() && !highlight.classes {
+            return Err(BlogError::MetadataError(format!(
+                "{}: a dark highlighting theme requires classes to be enabled", path.display()
+            )));
+        }
+
+        if let Some(legacy) = config.redirects.keys().find(|legacy| !legacy.starts_with('/')) {
+            return Err(BlogError::MetadataError(format!(
+                "{}: redirected path {:?} must start with a /", path.display(), legacy
+            )));
+        }
+
+        Ok(config)
+    }
+}
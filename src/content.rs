@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::error::BlogError;
+use crate::model::{ArticleMetaData, Author};
+use crate::validation::{Problem, Report};
+
+/// File extensions which are considered articles when scanning the content directory
+const EXTENSIONS: &[&str] = &["md", "markdown", "tera", "html"];
+
+/// A single article file from the content directory, split into
+/// its front matter and the (as of yet unrendered) body following it.
+pub struct ArticleSource {
+    pub path: PathBuf,
+    pub metadata: ArticleMetaData,
+    /// The front matter as written, kept around for pointing out where problems are
+    pub front_matter: String,
+    pub body: String,
+}
+
+impl ArticleSource {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ArticleSource, Problem> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| Problem::error(path, None, e.to_string()))?;
+
+        let (metadata, front_matter, body) = parse_front_matter(&text)
+            .map_err(|(line, message)| Problem::error(path, line, message))?;
+
+        Ok(ArticleSource {
+            path: path.to_path_buf(),
+            metadata,
+            front_matter: front_matter.to_string(),
+            body: body.to_string(),
+        })
+    }
+
+    /// Line number within the file of the given front matter key, if it is present.
+    /// The first line of the front matter is the second line of the file.
+    pub fn line_of(&self, key: &str) -> Option<usize> {
+        self.front_matter.lines()
+            .position(|line| {
+                line.trim_start()
+                    .strip_prefix(key)
+                    .map(|rest| rest.trim_start().starts_with(['=', ':']))
+                    .unwrap_or(false)
+            })
+            .map(|index| index + 2)
+    }
+}
+
+/// Recursively finds and parses every article in the given directory.
+/// Files are returned sorted by path, so the order is stable between runs.
+/// Files which can't be parsed are left out and added to the report instead.
+pub fn discover<P: AsRef<Path>>(dir: P, report: &mut Report) -> Vec<ArticleSource> {
+    let mut paths = Vec::new();
+    if let Err(e) = collect_paths(dir.as_ref(), &mut paths) {
+        report.add(e);
+    }
+    paths.sort();
+
+    paths.iter()
+        .filter_map(|path| ArticleSource::load(path).map_err(|problem| report.add(problem)).ok())
+        .collect()
+}
+
+/// Loads the authors registry, a TOML file of authors keyed by their ids
+pub fn load_authors<P: AsRef<Path>>(path: P) -> Result<BTreeMap<String, Author>, BlogError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|e| BlogError::IOError(format!("{}: {}", path.display(), e)))?;
+
+    let mut authors: BTreeMap<String, Author> = toml::from_str(&text).map_err(|e| {
+        BlogError::MetadataError(format!("{}: invalid authors registry: {}", path.display(), e))
+    })?;
+
+    let mut slugs: HashMap<String, String> = HashMap::new();
+    for (id, author) in authors.iter_mut() {
+        author.id = id.clone();
+        if author.slug.is_empty() {
+            author.slug = id.clone();
+        }
+
+        if let Some(existing) = slugs.insert(author.slug.clone(), id.clone()) {
+            return Err(BlogError::MetadataError(format!(
+                "{}: authors {:?} and {:?} share the slug {:?}",
+                path.display(), existing, id, author.slug
+            )));
+        }
+    }
+
+    Ok(authors)
+}
+
+fn collect_paths(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), BlogError> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| BlogError::IOError(format!("{}: {}", dir.display(), e)))?;
+
+    for entry in entries {
+        let path = entry?.path();
+
+        // Skip hidden files, which are usually editor swap files and the like
+        let hidden = path.file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.starts_with('.'))
+            .unwrap_or(true);
+
+        if hidden {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_paths(&path, paths)?;
+        } else if has_article_extension(&path) {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn has_article_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| EXTENSIONS.contains(&ext))
+        .unwrap_or(false)
+}
+
+/// Parse errors from toml and serde_yaml end with a position relative to the front matter,
+/// which is misleading when reported alongside the line within the file, so it is cut off.
+fn without_position(message: String) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message,
+    }
+}
+
+/// Splits a file into its front matter and body. Front matter is either TOML
+/// delimited by `+++` lines, or YAML delimited by `---` lines.
+/// Errors carry the line within the file at which the problem was found, if known.
+fn parse_front_matter(text: &str) -> Result<(ArticleMetaData, &str, &str), (Option<usize>, String)> {
+    let text = text.trim_start_matches('\u{feff}');
+
+    let delimiter = match text.lines().next().map(str::trim_end) {
+        Some("+++") => "+++",
+        Some("---") => "---",
+        _ => return Err((
+            Some(1),
+            "missing front matter, expected the file to start with +++ or ---".into()
+        )),
+    };
+
+    // Skip past the opening delimiter and find the line which closes the header
+    let header_start = text.find('\n').map(|i| i + 1).unwrap_or_else(|| text.len());
+    let mut offset = header_start;
+    let mut header_end = None;
+
+    for line in text[header_start..].split_terminator('\n') {
+        if line.trim_end() == delimiter {
+            header_end = Some((offset, offset + line.len() + 1));
+            break;
+        }
+        offset += line.len() + 1;
+    }
+
+    let (header_end, body_start) = header_end
+        .ok_or_else(|| (Some(1), format!("front matter is never closed, expected a line with {}", delimiter)))?;
+
+    let header = &text[header_start..header_end];
+    let body = &text[std::cmp::min(body_start, text.len())..];
+
+    // Line numbers reported by toml start from 0, while serde_yaml starts from 1,
+    // and either way the front matter itself starts on the second line of the file
+    let metadata = match delimiter {
+        "+++" => toml::from_str(header).map_err(|e| (
+            e.line_col().map(|(line, _)| line + 2),
+            format!("invalid TOML front matter: {}", without_position(e.to_string())),
+        ))?,
+        _ => serde_yaml::from_str(header).map_err(|e| (
+            e.location().map(|location| location.line() + 1),
+            format!("invalid YAML front matter: {}", without_position(e.to_string())),
+        ))?,
+    };
+
+    Ok((metadata, header, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(text: &str) -> ArticleSource {
+        let (metadata, front_matter, body) = parse_front_matter(text)
+            .unwrap_or_else(|(_, message)| panic!("{}", message));
+
+        ArticleSource {
+            path: PathBuf::from("article.md"),
+            metadata,
+            front_matter: front_matter.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn keys_are_found_in_yaml_front_matter() {
+        let source = source("---\ntitle: Hello\npublished: 0\nslug: hello\ntags:\n  - rust\nauthors: []\n---\nBody\n");
+
+        assert_eq!(source.line_of("title"), Some(2));
+        assert_eq!(source.line_of("slug"), Some(4));
+        assert_eq!(source.line_of("tags"), Some(5));
+        assert_eq!(source.line_of("series"), None);
+    }
+
+    #[test]
+    fn keys_are_found_in_toml_front_matter() {
+        let source = source("+++\ntitle = \"Hello\"\npublished = 0\n  slug= \"hello\"\nauthors = []\n+++\nBody\n");
+
+        assert_eq!(source.line_of("title"), Some(2));
+        assert_eq!(source.line_of("published"), Some(3));
+        assert_eq!(source.line_of("slug"), Some(4));
+    }
+
+    #[test]
+    fn keys_only_match_in_full() {
+        let source = source("---\ntitle: Hello\npublished: 0\nsummary: \"slug: no\"\nslug: hello\nauthors: []\n---\n");
+
+        assert_eq!(source.line_of("slug"), Some(5));
+        assert_eq!(source.line_of("pub"), None);
+    }
+}
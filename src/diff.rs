@@ -0,0 +1,383 @@
+use regex::Regex;
+use syntect::util::LinesWithEndings;
+
+lazy_static! {
+    static ref HUNK_HEADER: Regex = Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))? @@").unwrap();
+}
+
+/// Lines of unchanged context shown around every change when comparing two files
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Change {
+    Context,
+    Added,
+    Removed,
+}
+
+/// A line of a diff, numbered as it is in the old and the new version, whichever it appears in.
+/// The text includes the line break, but not the marker in front of it.
+pub struct DiffLine<'a> {
+    pub change: Change,
+    pub old: Option<usize>,
+    pub new: Option<usize>,
+    pub text: &'a str,
+}
+
+/// Changes to a single file, split into hunks of changed lines and the context around them
+pub struct FileDiff<'a> {
+    /// Path of the new version of the file, if the diff names it
+    pub path: Option<&'a str>,
+    pub hunks: Vec<Vec<DiffLine<'a>>>,
+}
+
+/// Parses a unified diff of a single file, such as the output of `git diff`
+pub fn parse(diff: &str) -> Result<FileDiff<'_>, String> {
+    let mut path = None;
+    let mut hunks = Vec::new();
+    let mut lines = LinesWithEndings::from(diff);
+
+    while let Some(line) = lines.next() {
+        if let Some(name) = line.strip_prefix("+++ ") {
+            if path.is_some() {
+                return Err("diff changes more than one file, expected a diff of a single file".into());
+            }
+
+            // Names may be followed by a timestamp, and git prefixes them with b/
+            let name = name.split('\t').next().unwrap_or(name).trim_end();
+            path = Some(name.strip_prefix("b/").unwrap_or(name));
+            continue;
+        }
+
+        let header = match HUNK_HEADER.captures(line) {
+            Some(header) => header,
+            None => continue,
+        };
+
+        let number = |index: usize| header.get(index).map_or(Ok(1), |number| number.as_str().parse::<usize>())
+            .map_err(|e| format!("invalid hunk header {:?}: {}", line.trim_end(), e));
+
+        let (mut old, mut old_count) = (number(1)?, number(2)?);
+        let (mut new, mut new_count) = (number(3)?, number(4)?);
+        let mut hunk = Vec::new();
+
+        // The counts in the header are what tells the end of the hunk apart from removed
+        // lines which happen to look like headers, such as a removed "-- comment"
+        while old_count > 0 || new_count > 0 {
+            let line = lines.next()
+                .ok_or_else(|| "diff ends in the middle of a hunk".to_string())?;
+
+            // Blank context lines sometimes lose their leading space along the way
+            let (change, text) = match line.chars().next() {
+                Some('+') => (Change::Added, &line[1..]),
+                Some('-') => (Change::Removed, &line[1..]),
+                Some(' ') => (Change::Context, &line[1..]),
+                Some('\r') | Some('\n') => (Change::Context, line),
+                Some('\\') => continue,
+                _ => return Err(format!("unexpected line {:?} in hunk", line.trim_end())),
+            };
+
+            let (old_number, new_number) = match change {
+                Change::Added => (None, Some(new)),
+                Change::Removed => (Some(old), None),
+                Change::Context => (Some(old), Some(new)),
+            };
+
+            if old_number.is_some() {
+                old += 1;
+                old_count = old_count.saturating_sub(1);
+            }
+
+            if new_number.is_some() {
+                new += 1;
+                new_count = new_count.saturating_sub(1);
+            }
+
+            hunk.push(DiffLine { change, old: old_number, new: new_number, text });
+        }
+
+        hunks.push(hunk);
+    }
+
+    match hunks.is_empty() {
+        true => Err("no hunks found in diff".into()),
+        false => Ok(FileDiff { path, hunks }),
+    }
+}
+
+/// Finds a point on the shortest way of editing `old` into `new`, halfway through the edits,
+/// by searching forwards from the start and backwards from the end until the two searches
+/// meet, as described by Myers. Takes space in proportion to the lengths of the two versions.
+fn split(old: &[&str], new: &[&str], same: &impl Fn(&str, &str) -> bool) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+
+    // Furthest line of the old version reached along each diagonal k, where k = x - y.
+    // The backward search works the same way, only counting from the ends of both versions.
+    let offset = max + 1;
+    let mut forward = vec![0isize; (2 * offset + 1) as usize];
+    let mut backward = vec![0isize; (2 * offset + 1) as usize];
+    let at = |k: isize| (offset + k) as usize;
+    let valid = |x: isize, y: isize| x >= 0 && y >= 0 && x <= n && y <= m;
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = match k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                true => forward[at(k + 1)],
+                false => forward[at(k - 1)] + 1,
+            };
+            let mut y = x - k;
+            let start = (x, y);
+
+            while x >= 0 && y >= 0 && x < n && y < m && same(old[x as usize], new[y as usize]) {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+
+            let reverse = delta - k;
+            if odd && reverse.abs() < d && x + backward[at(reverse)] >= n && valid(start.0, start.1) {
+                return Some((start.0 as usize, start.1 as usize));
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = match k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                true => backward[at(k + 1)],
+                false => backward[at(k - 1)] + 1,
+            };
+            let mut y = x - k;
+
+            while x >= 0 && y >= 0 && x < n && y < m && same(old[(n - 1 - x) as usize], new[(m - 1 - y) as usize]) {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+
+            let reverse = delta - k;
+            if !odd && reverse.abs() <= d && x + forward[at(reverse)] >= n && valid(n - x, m - y) {
+                return Some(((n - x) as usize, (m - y) as usize));
+            }
+        }
+    }
+
+    None
+}
+
+/// Appends the shortest sequence of changes turning `old` into `new`, splitting the search
+/// in two halves until only additions or removals remain. The lines both versions start and
+/// end with are set aside first, which makes small changes to large files cheap.
+fn shortest_edit(old: &[&str], new: &[&str], same: &impl Fn(&str, &str) -> bool, changes: &mut Vec<Change>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| same(a, b)).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old.iter().rev().zip(new.iter().rev()).take_while(|(a, b)| same(a, b)).count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    changes.extend(std::iter::repeat_n(Change::Context, prefix));
+
+    if !old.is_empty() && !new.is_empty() {
+        // Splitting at either end would never finish, so that's taken as the versions having nothing in common
+        let halves = split(old, new, same).filter(|&(x, y)| (x, y) != (0, 0) && (x, y) != (old.len(), new.len()));
+
+        if let Some((x, y)) = halves {
+            shortest_edit(&old[..x], &new[..y], same, changes);
+            shortest_edit(&old[x..], &new[y..], same, changes);
+            changes.extend(std::iter::repeat_n(Change::Context, suffix));
+            return;
+        }
+    }
+
+    changes.extend(std::iter::repeat_n(Change::Removed, old.len()));
+    changes.extend(std::iter::repeat_n(Change::Added, new.len()));
+    changes.extend(std::iter::repeat_n(Change::Context, suffix));
+}
+
+/// Compares two versions of a file line by line, treating everything not part of the shortest
+/// way of editing one into the other as changed. Identical files are shown in their entirety,
+/// and otherwise only the changes and a few lines of context around them are.
+pub fn compare<'a>(before: &'a str, after: &'a str) -> Vec<Vec<DiffLine<'a>>> {
+    let old: Vec<&str> = LinesWithEndings::from(before).collect();
+    let new: Vec<&str> = LinesWithEndings::from(after).collect();
+
+    // A missing line break at the end of either file doesn't count as a change
+    let same = |a: &str, b: &str| a.trim_end_matches(['\r', '\n']) == b.trim_end_matches(['\r', '\n']);
+
+    let mut changes = Vec::with_capacity(old.len().max(new.len()));
+    shortest_edit(&old, &new, &same, &mut changes);
+
+    // Removed lines go before the lines added in their place, the same way diff does it
+    let mut start = 0;
+    while start < changes.len() {
+        let end = changes[start..].iter()
+            .position(|&change| change == Change::Context)
+            .map_or(changes.len(), |length| start + length);
+
+        changes[start..end].sort_by_key(|&change| change != Change::Removed);
+        start = end + 1;
+    }
+
+    let mut lines = Vec::with_capacity(changes.len());
+    let (mut i, mut j) = (0, 0);
+    for change in changes {
+        let (old_number, new_number, text) = match change {
+            Change::Context => (Some(i + 1), Some(j + 1), new[j]),
+            Change::Added => (None, Some(j + 1), new[j]),
+            Change::Removed => (Some(i + 1), None, old[i]),
+        };
+
+        if old_number.is_some() {
+            i += 1;
+        }
+
+        if new_number.is_some() {
+            j += 1;
+        }
+
+        lines.push(DiffLine { change, old: old_number, new: new_number, text });
+    }
+
+    let changes: Vec<usize> = lines.iter()
+        .enumerate()
+        .filter(|(_, line)| line.change != Change::Context)
+        .map(|(index, _)| index)
+        .collect();
+
+    if changes.is_empty() {
+        return vec![lines];
+    }
+
+    // Lines close enough to a change are kept, and gaps between them split the hunks
+    let mut hunks: Vec<Vec<DiffLine<'a>>> = Vec::new();
+    let mut previous: Option<usize> = None;
+    for (index, line) in lines.into_iter().enumerate() {
+        let near = changes.iter().any(|&change| index + CONTEXT >= change && index <= change + CONTEXT);
+        if !near {
+            continue;
+        }
+
+        match previous {
+            Some(previous) if previous + 1 == index => hunks.last_mut().unwrap().push(line),
+            _ => hunks.push(vec![line]),
+        }
+        previous = Some(index);
+    }
+
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(hunks: &[Vec<DiffLine<'_>>]) -> String {
+        hunks.iter()
+            .map(|hunk| hunk.iter()
+                .map(|line| match line.change {
+                    Change::Context => ' ',
+                    Change::Added => '+',
+                    Change::Removed => '-',
+                })
+                .collect::<String>())
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+
+    /// Length of the longest common subsequence, the slow but obvious way
+    fn common(old: &[u8], new: &[u8]) -> usize {
+        let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in 0..old.len() {
+            for j in 0..new.len() {
+                lengths[i + 1][j + 1] = match old[i] == new[j] {
+                    true => lengths[i][j] + 1,
+                    false => lengths[i][j + 1].max(lengths[i + 1][j]),
+                };
+            }
+        }
+        lengths[old.len()][new.len()]
+    }
+
+    #[test]
+    fn parses_hunks_with_line_numbers() {
+        let diff = "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,3 +1,3 @@\n fn main() {\n-    one();\n+    two();\n }\n";
+        let parsed = parse(diff).unwrap();
+
+        assert_eq!(parsed.path, Some("src/main.rs"));
+        assert_eq!(changes(&parsed.hunks), " -+ ");
+
+        let numbers: Vec<_> = parsed.hunks[0].iter().map(|line| (line.old, line.new)).collect();
+        assert_eq!(numbers, vec![(Some(1), Some(1)), (Some(2), None), (None, Some(2)), (Some(3), Some(3))]);
+    }
+
+    #[test]
+    fn ignores_missing_newline_markers() {
+        let diff = "@@ -1 +1 @@\n-old\n\\ No newline at end of file\n+new\n\\ No newline at end of file\n";
+        let parsed = parse(diff).unwrap();
+
+        assert_eq!(changes(&parsed.hunks), "-+");
+        assert_eq!(parsed.hunks[0][1].text, "new\n");
+    }
+
+    #[test]
+    fn counts_tell_removed_lines_from_headers() {
+        let diff = "@@ -1,2 +1 @@\n--- comment\n kept\n@@ -10 +9,0 @@\n-x\n";
+        let parsed = parse(diff).unwrap();
+
+        assert_eq!(changes(&parsed.hunks), "- |-");
+        assert_eq!(parsed.hunks[1][0].old, Some(10));
+    }
+
+    #[test]
+    fn rejects_broken_diffs() {
+        assert!(parse("no hunks here\n").is_err());
+        assert!(parse("@@ -1,3 +1,3 @@\n context\n").is_err());
+        assert!(parse("+++ a\n@@ -1 +1 @@\n-x\n+y\n+++ b\n").is_err());
+    }
+
+    #[test]
+    fn compares_files_with_context() {
+        let before = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let after = "1\nchanged\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\nadded\n";
+
+        assert_eq!(changes(&compare(before, after)), " -+   |   +");
+        assert_eq!(changes(&compare(before, before)), " ".repeat(12));
+    }
+
+    #[test]
+    fn missing_line_break_at_the_end_is_no_change() {
+        assert_eq!(changes(&compare("a\nb\n", "a\nb")), "  ");
+    }
+
+    #[test]
+    fn removals_come_before_additions() {
+        assert_eq!(changes(&compare("a\nb\nc\n", "x\ny\nc\n")), "--++ ");
+    }
+
+    #[test]
+    fn finds_the_shortest_edit() {
+        // Small pseudo-random versions over a tiny alphabet, so that they share plenty of lines
+        let mut seed: u32 = 1;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as usize
+        };
+
+        for _ in 0..500 {
+            let old: Vec<u8> = (0..next() % 30).map(|_| b'a' + (next() % 4) as u8).collect();
+            let new: Vec<u8> = (0..next() % 30).map(|_| b'a' + (next() % 4) as u8).collect();
+
+            let text = |lines: &[u8]| lines.iter().map(|&line| format!("{}\n", line as char)).collect::<String>();
+            let (before, after) = (text(&old), text(&new));
+            let hunks = compare(&before, &after);
+
+            let kept = hunks.iter().flatten().filter(|line| line.change == Change::Context).count();
+            let edits = hunks.iter().flatten().filter(|line| line.change != Change::Context).count();
+            assert_eq!(edits, old.len() + new.len() - 2 * common(&old, &new), "{:?} to {:?}", before, after);
+
+            if edits > 0 {
+                assert!(kept <= common(&old, &new));
+            }
+        }
+    }
+}
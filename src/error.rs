@@ -1,4 +1,5 @@
 use actix_web::{HttpResponse, ResponseError};
+use crate::validation::Report;
 
 #[derive(Fail, Debug)]
 pub enum BlogError {
@@ -8,13 +9,20 @@ pub enum BlogError {
     #[fail(display = "Missing content: {:?}", _0)]
     MissingContent(String),
 
+    #[fail(display = "Metadata Error: {:?}", _0)]
+    MetadataError(String),
+
     #[fail(display = "I/O Error: {:?}", _0)]
-    IOError(String)
+    IOError(String),
+
+    #[fail(display = "{}", _0)]
+    ValidationError(Report),
 }
 
 impl ResponseError for BlogError {
     fn error_response(&self) -> HttpResponse {
         match self {
+            BlogError::MissingContent(_) => HttpResponse::NotFound().finish(),
             _ => HttpResponse::InternalServerError().finish(),
         }
     }
@@ -22,10 +30,19 @@ impl ResponseError for BlogError {
 
 impl From<tera::Error> for BlogError {
     fn from(e: tera::Error) -> Self {
-        BlogError::TemplateError(format!("{}", e))
+        BlogError::TemplateError(describe_tera_error(&e))
     }
 }
 
+/// Tera wraps the actual cause of a failure in a chain of errors, the outermost
+/// of which is rarely helpful on its own, so include the entire chain.
+pub fn describe_tera_error(e: &tera::Error) -> String {
+    e.iter()
+        .map(|cause| cause.to_string())
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
 impl From<std::io::Error> for BlogError {
     fn from(e: std::io::Error) -> Self {
         BlogError::IOError(format!("{}", e))
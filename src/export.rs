@@ -0,0 +1,195 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use tera::Context;
+use crate::archive;
+use crate::error::BlogError;
+use crate::feed::{self, FeedFormat};
+use crate::highlighter;
+use crate::model::article_url;
+use crate::pages;
+use crate::site::{Site, STATIC_DIR};
+use crate::sitemap;
+
+/// Writes files to a directory laid out like the URLs of the live blog,
+/// so it can be served by anything capable of serving static files.
+struct Exporter {
+    out: PathBuf,
+    written: usize,
+}
+
+impl Exporter {
+    /// Writes a file for the given URL path. Paths without an extension, such as
+    /// `/articles/hello-world`, are written as an index.html inside a directory.
+    fn write(&mut self, url: &str, body: String) -> Result<(), BlogError> {
+        let relative = url.trim_start_matches('/');
+        let mut path = self.out.join(relative);
+
+        if relative.is_empty() || Path::new(relative).extension().is_none() {
+            path.push("index.html");
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| BlogError::IOError(format!("{}: {}", parent.display(), e)))?;
+        }
+
+        fs::write(&path, body).map_err(|e| BlogError::IOError(format!("{}: {}", path.display(), e)))?;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn copy_dir(&mut self, from: &Path, to: &Path) -> Result<(), BlogError> {
+        fs::create_dir_all(to).map_err(|e| BlogError::IOError(format!("{}: {}", to.display(), e)))?;
+
+        let entries = fs::read_dir(from)
+            .map_err(|e| BlogError::IOError(format!("{}: {}", from.display(), e)))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            let target = to.join(path.file_name().unwrap());
+
+            if path.is_dir() {
+                self.copy_dir(&path, &target)?;
+            } else {
+                fs::copy(&path, &target)
+                    .map_err(|e| BlogError::IOError(format!("{}: {}", path.display(), e)))?;
+                self.written += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Left in every exported directory, marking it as safe to empty when building again
+const MARKER: &str = ".bloggerino";
+
+/// Empties the output directory, so nothing renamed or unpublished since the last build lingers.
+/// Only directories left by a previous build are emptied, so a mistyped path can't wipe anything else.
+fn prepare(out: &Path) -> Result<(), BlogError> {
+    let entries: Vec<PathBuf> = match fs::read_dir(out) {
+        Ok(entries) => entries.map(|entry| entry.map(|entry| entry.path())).collect::<Result<_, _>>()?,
+        Err(_) => return Ok(()),
+    };
+
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    if !out.join(MARKER).is_file() {
+        return Err(BlogError::IOError(format!(
+            "{}: refusing to build into a directory which isn't empty and wasn't built into before", out.display()
+        )));
+    }
+
+    for path in entries {
+        let removed = match path.is_dir() {
+            true => fs::remove_dir_all(&path),
+            false => fs::remove_file(&path),
+        };
+        removed.map_err(|e| BlogError::IOError(format!("{}: {}", path.display(), e)))?;
+    }
+
+    Ok(())
+}
+
+/// Static hosts generally can't answer with redirects, so former slugs and
+/// the redirect table are exported as pages redirecting as soon as they load
+fn redirect_page(site: &Site, target: &str) -> String {
+    let url = match target.starts_with('/') {
+        true => format!("{}{}", site.config.base_url, target),
+        false => target.to_string(),
+    };
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n\t<meta charset=\"utf-8\">\n\t<meta http-equiv=\"refresh\" content=\"0; url={url}\">\n\t<link rel=\"canonical\" href=\"{url}\">\n</head>\n<body><a href=\"{url}\">{url}</a></body>\n</html>",
+        url = url
+    )
+}
+
+/// Renders every page of the site into the `out` directory and copies the static files
+/// alongside them, returning the number of files written. Pages are rendered without the
+/// server statistics, which leaves out the sparklines and the websocket connection entirely,
+/// as well as the link to the search page, which can't be served statically.
+/// Variants of pages selected by query string, such as per-author feeds, aren't exported.
+/// Whatever a previous build left in the directory is removed first.
+pub fn build<P: AsRef<Path>>(site: &Site, out: P) -> Result<usize, BlogError> {
+    let out = out.as_ref();
+    prepare(out)?;
+
+    let mut exporter = Exporter {
+        out: out.to_path_buf(),
+        written: 0,
+    };
+
+    let total = site.world.listed().len().div_ceil(site.config.page_size);
+    for page in 1..=total.max(1) {
+        exporter.write(&pages::frontpage_url(page), pages::frontpage(site, Context::new(), page)?)?;
+    }
+
+    for article in &site.world.articles {
+        exporter.write(&article.url, pages::article(site, Context::new(), &article.slug)?)?;
+    }
+
+    for article in &site.world.articles {
+        for alias in &article.aliases {
+            let url = article_url(&article.language, &site.config.language, alias);
+            exporter.write(&url, redirect_page(site, &article.url))?;
+        }
+    }
+
+    for (path, target) in &site.config.redirects {
+        exporter.write(path, redirect_page(site, target))?;
+    }
+
+    let months = archive::by_month(&site.world.listed());
+    exporter.write("/archive", pages::archive(site, Context::new())?)?;
+
+    for year in archive::years(&months) {
+        exporter.write(&format!("/archive/{}", year), pages::archive_year(site, Context::new(), year)?)?;
+    }
+
+    for month in &months {
+        let url = format!("/archive/{}/{}", month.year, month.month);
+        exporter.write(&url, pages::archive_month(site, Context::new(), month.year, month.month)?)?;
+    }
+
+    exporter.write("/tags", pages::tags(site, Context::new())?)?;
+
+    for slug in site.world.tags.keys() {
+        exporter.write(&format!("/tags/{}", slug), pages::tag(site, Context::new(), slug)?)?;
+    }
+
+    for slug in site.world.categories.keys() {
+        exporter.write(&format!("/categories/{}", slug), pages::category(site, Context::new(), slug)?)?;
+    }
+
+    for slug in site.world.series.keys() {
+        exporter.write(&format!("/series/{}", slug), pages::series(site, Context::new(), slug)?)?;
+    }
+
+    for slug in site.world.authors.keys() {
+        exporter.write(&format!("/authors/{}", slug), pages::author(site, Context::new(), slug)?)?;
+    }
+
+    for format in &[FeedFormat::Atom, FeedFormat::Rss] {
+        exporter.write(format.path(), feed::render(site, *format, None)?)?;
+    }
+
+    exporter.write(feed::JSON_FEED_PATH, feed::render_json(site, None, 1)?)?;
+    exporter.write(sitemap::SITEMAP_PATH, sitemap::render_sitemap(site)?)?;
+    exporter.write("/robots.txt", sitemap::render_robots(site))?;
+
+    let static_dir = exporter.out.join("static");
+    exporter.copy_dir(Path::new(STATIC_DIR), &static_dir)?;
+
+    for theme in highlighter::themes() {
+        if let Some(css) = highlighter::stylesheet(theme) {
+            exporter.write(&format!("/static/highlight/{}.css", theme), css)?;
+        }
+    }
+
+    let marker = out.join(MARKER);
+    fs::write(&marker, "").map_err(|e| BlogError::IOError(format!("{}: {}", marker.display(), e)))?;
+
+    Ok(exporter.written)
+}
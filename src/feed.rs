@@ -0,0 +1,269 @@
+use chrono::{SecondsFormat, TimeZone, Utc};
+use regex::{Captures, Regex};
+use tera::Context;
+use crate::analysis::plain_text;
+use crate::error::BlogError;
+use crate::highlighter;
+use crate::model::{current_time, Article, Author};
+use crate::pagination::paginate;
+use crate::site::Site;
+
+lazy_static! {
+    static ref LINK: Regex = Regex::new(r#"(\s(?:href|src)\s*=\s*")([/#])([^"]*)""#).unwrap();
+}
+
+/// Syndication formats, each rendered by its own template
+#[derive(Clone, Copy)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    pub fn template(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "feeds/atom.tera",
+            FeedFormat::Rss => "feeds/rss.tera",
+        }
+    }
+
+    pub fn path(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "/feed.atom",
+            FeedFormat::Rss => "/feed.xml",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "application/atom+xml",
+            FeedFormat::Rss => "application/rss+xml",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    /// Slug of an author to limit the feed to
+    pub author: Option<String>,
+    /// Page of the feed to show, counting from 1. Only the JSON feed is paginated
+    pub page: Option<usize>,
+}
+
+/// An article along with its body as included in feeds
+#[derive(Serialize)]
+struct FeedEntry<'a> {
+    #[serde(flatten)]
+    article: &'a Article,
+    content: String,
+}
+
+/// Body of the article as included in feeds. Feed readers show articles away from the blog,
+/// where links relative to it either resolve against the feed or not at all, so they're made
+/// absolute, and links within the article itself are pointed at the article on the blog.
+/// The style sheets coloring code don't come along either, so code is colored inline instead.
+fn feed_body(site: &Site, article: &Article) -> String {
+    let base_url = &site.config.base_url;
+    let body = match site.config.highlight.classes {
+        true => highlighter::inline_colors(&article.body, &site.config.highlight.theme),
+        false => article.body.clone(),
+    };
+
+    LINK.replace_all(&body, |captures: &Captures<'_>| {
+        let (attribute, start, rest) = (&captures[1], &captures[2], &captures[3]);
+
+        match start {
+            "#" => format!("{}{}{}#{}\"", attribute, base_url, article.url, rest),
+            // Protocol-relative links are absolute already
+            _ if rest.starts_with('/') => captures[0].to_string(),
+            _ => format!("{}{}/{}\"", attribute, base_url, rest),
+        }
+    }).into_owned()
+}
+
+/// Renders a feed of the most recently published articles, optionally
+/// limited to the articles written by a single author
+pub fn render(site: &Site, format: FeedFormat, author: Option<&str>) -> Result<String, BlogError> {
+    let (author, articles): (Option<&Author>, Vec<&Article>) = match author {
+        Some(slug) => {
+            let profile = site.world.find_author(slug)?;
+            (Some(&profile.author), site.world.articles_by(profile))
+        }
+        None => (None, site.world.listed()),
+    };
+
+    let articles: Vec<&Article> = articles.into_iter().take(site.config.feed_size).collect();
+    let updated = articles.iter().map(|article| article.updated).max().unwrap_or_else(current_time);
+
+    let entries: Vec<FeedEntry<'_>> = articles.iter()
+        .map(|article| FeedEntry { article, content: feed_body(site, article) })
+        .collect();
+
+    let self_url = match author {
+        Some(author) => format!("{}{}?author={}", site.config.base_url, format.path(), author.slug),
+        None => format!("{}{}", site.config.base_url, format.path()),
+    };
+
+    let mut ctx = Context::new();
+    ctx.insert("config", &site.config);
+    ctx.insert("author", &author);
+    ctx.insert("articles", &entries);
+    ctx.insert("updated", &updated);
+    ctx.insert("self_url", &self_url);
+
+    Ok(site.tera.render(format.template(), &ctx)?)
+}
+
+pub const JSON_FEED_PATH: &str = "/feed.json";
+
+/// Length of the plain text summaries included in JSON feeds, in characters
+const SUMMARY_LENGTH: usize = 300;
+
+/// Top-level object of a JSON Feed, see https://jsonfeed.org/version/1.1
+#[derive(Serialize)]
+pub struct JsonFeed<'a> {
+    pub version: &'static str,
+    pub title: String,
+    pub home_page_url: String,
+    pub feed_url: String,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub description: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_url: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<JsonFeedAuthor<'a>>,
+    pub items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct JsonFeedItem<'a> {
+    pub id: String,
+    pub url: String,
+    pub title: &'a str,
+    pub content_html: String,
+    pub summary: String,
+    pub date_published: String,
+    pub date_modified: String,
+    pub authors: Vec<JsonFeedAuthor<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+pub struct JsonFeedAuthor<'a> {
+    pub name: &'a str,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+}
+
+impl<'a> JsonFeedAuthor<'a> {
+    fn new(author: &'a Author, base_url: &str) -> Self {
+        JsonFeedAuthor {
+            name: &author.name,
+            url: format!("{}/authors/{}", base_url, author.slug),
+            // Avatars are usually given relative to the site itself
+            avatar: author.avatar.as_ref().map(|avatar| match avatar.starts_with('/') {
+                true => format!("{}{}", base_url, avatar),
+                false => avatar.clone(),
+            }),
+        }
+    }
+}
+
+fn rfc3339(timestamp: u64) -> String {
+    Utc.timestamp(timestamp as i64, 0).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Plain text version of the excerpt of an article, cut at a word boundary
+fn summarize(html: &str) -> String {
+    let text = plain_text(html);
+
+    let mut summary = String::new();
+    for word in text.split_whitespace() {
+        if summary.chars().count() + word.chars().count() >= SUMMARY_LENGTH {
+            summary.push('…');
+            break;
+        }
+
+        if !summary.is_empty() {
+            summary.push(' ');
+        }
+        summary.push_str(word);
+    }
+
+    summary
+}
+
+/// Renders a page of the JSON feed, optionally limited to the articles written by a single author.
+/// Unlike the XML feeds, every article is reachable by following `next_url` from the first page.
+pub fn render_json(site: &Site, author: Option<&str>, page: usize) -> Result<String, BlogError> {
+    let base_url = &site.config.base_url;
+
+    let (author, articles): (Option<&Author>, Vec<&Article>) = match author {
+        Some(slug) => {
+            let profile = site.world.find_author(slug)?;
+            (Some(&profile.author), site.world.articles_by(profile))
+        }
+        None => (None, site.world.listed()),
+    };
+
+    let feed_url = |page: usize| {
+        let mut url = format!("{}{}", base_url, JSON_FEED_PATH);
+        let mut separator = '?';
+
+        if let Some(author) = author {
+            url.push_str(&format!("{}author={}", separator, author.slug));
+            separator = '&';
+        }
+
+        if page > 1 {
+            url.push_str(&format!("{}page={}", separator, page));
+        }
+
+        url
+    };
+
+    let (articles, pagination) = paginate(&articles, page, site.config.feed_size, feed_url)
+        .ok_or_else(|| BlogError::MissingContent("no page with that number found".into()))?;
+
+    let items = articles.iter()
+        .map(|article| {
+            let url = format!("{}{}", base_url, article.url);
+
+            JsonFeedItem {
+                id: url.clone(),
+                url,
+                title: &article.title,
+                content_html: feed_body(site, article),
+                summary: summarize(&article.excerpt),
+                date_published: rfc3339(article.published),
+                date_modified: rfc3339(article.updated),
+                authors: article.authors.iter()
+                    .map(|author| JsonFeedAuthor::new(author, base_url))
+                    .collect(),
+                tags: article.tags.iter().map(|tag| tag.name.as_str()).collect(),
+            }
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: match author {
+            Some(author) => format!("{} • {}", site.config.title, author.name),
+            None => site.config.title.clone(),
+        },
+        home_page_url: match author {
+            Some(author) => format!("{}/authors/{}", base_url, author.slug),
+            None => format!("{}/", base_url),
+        },
+        feed_url: feed_url(page),
+        description: &site.config.description,
+        next_url: pagination.next.map(|next| next.url),
+        authors: author.map(|author| JsonFeedAuthor::new(author, base_url)).into_iter().collect(),
+        items,
+    };
+
+    serde_json::to_string(&feed)
+        .map_err(|e| BlogError::TemplateError(format!("failed to serialize JSON feed: {}", e)))
+}
@@ -1,104 +1,811 @@
 use std::cmp::max;
+use std::borrow::Cow;
+use std::fs;
 use std::path::Path;
-use std::io::BufRead;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use regex::{Captures, Regex};
+use crate::analysis::escape;
+use crate::config::Highlighting;
+use crate::diff::{self, Change, DiffLine};
 use tera::{Result, Value};
 use syntect::{
-    parsing::SyntaxSet,
+    parsing::{BasicScopeStackOp, ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet},
     html::{
         IncludeBackground,
         start_highlighted_html_snippet,
         append_highlighted_html_for_styled_line
     },
-    highlighting::{Color, Theme, ThemeSet},
-    easy::HighlightFile
+    highlighting::{Color, FontStyle, Highlighter, ScopeSelector, Theme, ThemeSet},
+    easy::HighlightLines,
+    util::LinesWithEndings
 };
 
 lazy_static! {
     static ref SYNTAXSET: SyntaxSet = SyntaxSet::load_defaults_newlines();
     static ref THEMESET: ThemeSet = ThemeSet::load_defaults();
+    static ref BLOCK_TAG: Regex = Regex::new(r#"<(/?)(span|code|pre)\b(?:[^>]*?\bclass="([^"]*)")?[^>]*>"#).unwrap();
+    /// Markers delimiting named regions of a source file, such as `// ANCHOR: setup`.
+    /// Only comments on lines of their own count, so code mentioning markers is left alone.
+    static ref ANCHOR: Regex = Regex::new(
+        r"^\s*(?://|#|--|/\*|<!--)\s*ANCHOR(_END)?:\s*([\w-]+)\s*(?:\*/|-->)?\s*$"
+    ).unwrap();
 }
 
-const DEFAULT_THEME: &str = "base16-ocean.dark";
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// Prefix of the classes named after syntax scopes, keeping them apart from the rest of the page
+const CLASS_PREFIX: &str = "hl-";
 
 fn accentuate(color: Color, degree: u8) -> String {
-    format!("rgba({}, {}, {}, {})",
+    format!("rgba({}, {}, {}, {:.2})",
         max(color.r, degree) - degree,
         max(color.g, degree) - degree,
         max(color.b, degree) - degree,
-        color.a
+        f32::from(color.a) / 255.0
     )
 }
 
-// This function is lifted more or less one-to-one from syntect, but adds the <code></code>
-// which allows us to do nice line numbering on em
-fn syntax_highlighter<P: AsRef<Path>>(path: P, theme: &Theme) -> std::io::Result<String> {
-    let mut highlighter = HighlightFile::new(path, &SYNTAXSET, theme)?;
-    let (mut output, bg) = start_highlighted_html_snippet(theme);
+/// Background used for every second line to distinguish them
+fn alternate_background(theme: &Theme) -> Option<String> {
+    theme.settings.background.map(|c| accentuate(c, 5))
+}
+
+/// Background of emphasized lines, which is the line highlight of the
+/// theme, or failing that a more pronounced alternate background
+fn emphasis_background(theme: &Theme) -> Option<String> {
+    theme.settings.line_highlight.map(|c| accentuate(c, 0))
+        .or_else(|| theme.settings.background.map(|c| accentuate(c, 20)))
+}
+
+fn find_theme(name: &str) -> Result<&'static Theme> {
+    THEMESET.themes.get(name).ok_or_else(|| tera::Error::from(format!(
+        "unknown theme {:?}, expected one of {}",
+        name, THEMESET.themes.keys().cloned().collect::<Vec<_>>().join(", ")
+    )))
+}
+
+pub fn has_theme(name: &str) -> bool {
+    THEMESET.themes.contains_key(name)
+}
+
+/// Names of every theme a style sheet can be generated for
+pub fn themes() -> impl Iterator<Item = &'static str> {
+    THEMESET.themes.keys().map(String::as_str)
+}
+
+/// How highlighted code is colored. Inline styles take their colors from a single theme,
+/// while classes name the syntax scopes of every token, and leave the coloring to the
+/// style sheets generated for every theme. Those can be swapped without highlighting again.
+#[derive(Clone, Copy)]
+enum Colors {
+    Inline(&'static Theme),
+    Classes,
+}
+
+/// Highlights lines one at a time, carrying the state of the syntax over from one line to the next
+enum LineHighlighter<'a> {
+    Inline {
+        lines: HighlightLines<'a>,
+        background: Color,
+    },
+    Classes {
+        state: ParseState,
+        scopes: ScopeStack,
+    },
+}
+
+/// Opens a span with a class for every atom of the scope, so "source.rust" becomes "hl-source hl-rust"
+fn open_scope(output: &mut String, scope: Scope) {
+    let classes: Vec<String> = scope.build_string()
+        .split('.')
+        .map(|atom| format!("{}{}", CLASS_PREFIX, atom))
+        .collect();
+
+    output.push_str(&format!("<span class=\"{}\">", escape(&classes.join(" "))));
+}
+
+impl<'a> LineHighlighter<'a> {
+    fn new(syntax: &SyntaxReference, colors: Colors) -> Self {
+        match colors {
+            Colors::Inline(theme) => LineHighlighter::Inline {
+                lines: HighlightLines::new(syntax, theme),
+                background: theme.settings.background.unwrap_or(Color::WHITE),
+            },
+            Colors::Classes => LineHighlighter::Classes {
+                state: ParseState::new(syntax),
+                scopes: ScopeStack::new(),
+            },
+        }
+    }
+
+    /// Highlights the line, which must end in a line break, and appends it without the line break,
+    /// so callouts and the like can still be added to the end of it. With classes, the spans of
+    /// scopes spanning several lines are closed at the end of every line and opened again at the
+    /// start of the next, so that every line can be wrapped in an element of its own.
+    fn append(&mut self, line: &str, output: &mut String) {
+        let end = line.trim_end_matches(['\r', '\n']).len();
+
+        match self {
+            LineHighlighter::Inline { lines, background } => {
+                let mut regions = lines.highlight(line, &SYNTAXSET);
+                if let Some((_, text)) = regions.last_mut() {
+                    *text = text.trim_end_matches(['\r', '\n']);
+                }
+
+                append_highlighted_html_for_styled_line(&regions[..], IncludeBackground::IfDifferent(*background), output);
+            }
+            LineHighlighter::Classes { state, scopes } => {
+                for &scope in scopes.as_slice() {
+                    open_scope(output, scope);
+                }
+
+                let mut position = 0;
+                for (index, operation) in state.parse_line(line, &SYNTAXSET) {
+                    output.push_str(&escape(&line[position.min(end)..index.min(end)]));
+                    position = index;
+
+                    scopes.apply_with_hook(&operation, |operation, _| match operation {
+                        BasicScopeStackOp::Push(scope) => open_scope(output, scope),
+                        BasicScopeStackOp::Pop => output.push_str("</span>"),
+                    });
+                }
+
+                output.push_str(&escape(&line[position.min(end)..end]));
+                for _ in scopes.as_slice() {
+                    output.push_str("</span>");
+                }
+            }
+        }
+    }
+}
+
+/// Opens the <pre> element of a block, which with inline styles also colors the line numbers
+fn start_block(colors: Colors, class: Option<&str>) -> String {
+    match colors {
+        Colors::Inline(theme) => {
+            let (output, _) = start_highlighted_html_snippet(theme);
+            let mut output = match class {
+                Some(class) => output.replacen("<pre", &format!("<pre class=\"{}\"", class), 1),
+                None => output,
+            };
+
+            output.push_str(&gutter_style(theme));
+            output
+        }
+        Colors::Classes => match class {
+            Some(class) => format!("<pre class=\"highlight {}\">\n", class),
+            None => "<pre class=\"highlight\">\n".into(),
+        },
+    }
+}
+
+/// Attributes of alternate and emphasized lines
+fn line_attributes(colors: Colors) -> (String, String) {
+    match colors {
+        Colors::Inline(theme) => (
+            alternate_background(theme)
+                .map(|color| format!("style=\"background-color: {};\"", color))
+                .unwrap_or_default(),
+            emphasis_background(theme)
+                .map(|color| format!("class=\"emphasized\" style=\"background-color: {};\"", color))
+                .unwrap_or_else(|| "class=\"emphasized\"".into()),
+        ),
+        Colors::Classes => ("class=\"alternate\"".into(), "class=\"emphasized\"".into()),
+    }
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// Colors code highlighted with classes inline using the theme, for places the style sheets
+/// can't reach, such as feed readers. Scopes are matched the way syntect matches them when
+/// highlighting, so the result looks the same as if the code had been colored inline to begin
+/// with, and anything outside of highlighted blocks is left as it is.
+pub fn inline_colors(html: &str, theme: &str) -> String {
+    let theme = match THEMESET.themes.get(theme) {
+        Some(theme) => theme,
+        None => return html.to_string(),
+    };
+
+    let highlighter = Highlighter::new(theme);
+    let background = theme.settings.background.unwrap_or(Color::WHITE);
+    let (alternate, emphasis) = (alternate_background(theme), emphasis_background(theme));
+
+    let mut inside = false;
+    // Scopes of the open spans, or None for spans of other kinds, such as callouts
+    let mut spans: Vec<Option<Scope>> = Vec::new();
 
+    BLOCK_TAG.replace_all(html, |captures: &Captures<'_>| {
+        let tag = &captures[0];
+        let closing = !captures[1].is_empty();
+        let classes: Vec<&str> = captures.get(3).map_or(Vec::new(), |classes| classes.as_str().split_whitespace().collect());
+
+        match &captures[2] {
+            "pre" if closing => {
+                inside = false;
+                tag.to_string()
+            }
+            "pre" if classes.contains(&"highlight") => {
+                inside = true;
+                spans.clear();
+                let mut style = format!("background-color:{};", hex(background));
+                if let Some(foreground) = theme.settings.foreground {
+                    style.push_str(&format!("color:{};", hex(foreground)));
+                }
+                format!("{} style=\"{}\">", tag.trim_end_matches('>'), style)
+            }
+            _ if !inside => tag.to_string(),
+            "code" if !closing => {
+                let color = match (classes.contains(&"emphasized"), classes.contains(&"alternate")) {
+                    (true, _) => emphasis.as_ref(),
+                    (_, true) => alternate.as_ref(),
+                    _ => None,
+                };
+
+                match color {
+                    Some(color) => format!("{} style=\"background-color: {};\">", tag.trim_end_matches('>'), color),
+                    None => tag.to_string(),
+                }
+            }
+            "span" if closing => {
+                spans.pop();
+                tag.to_string()
+            }
+            "span" => {
+                let atoms: Vec<&str> = classes.iter().filter_map(|class| class.strip_prefix(CLASS_PREFIX)).collect();
+                let scope = match atoms.is_empty() {
+                    true => None,
+                    false => Scope::new(&atoms.join(".")).ok(),
+                };
+                spans.push(scope);
+
+                if scope.is_none() {
+                    return tag.to_string();
+                }
+
+                let stack: Vec<Scope> = spans.iter().flatten().cloned().collect();
+                let style = highlighter.style_for_stack(&stack);
+
+                let mut css = format!("color:{};", hex(style.foreground));
+                if style.background != background {
+                    css.push_str(&format!("background-color:{};", hex(style.background)));
+                }
+                if style.font_style.contains(FontStyle::BOLD) {
+                    css.push_str("font-weight:bold;");
+                }
+                if style.font_style.contains(FontStyle::ITALIC) {
+                    css.push_str("font-style:italic;");
+                }
+                if style.font_style.contains(FontStyle::UNDERLINE) {
+                    css.push_str("text-decoration:underline;");
+                }
+
+                format!("<span style=\"{}\">", css)
+            }
+            _ => tag.to_string(),
+        }
+    }).into_owned()
+}
+
+/// Class selector for a scope atom, escaping anything CSS doesn't allow in class names, such as c++
+fn class_selector(atom: &str) -> String {
+    let mut selector = format!(".{}", CLASS_PREFIX);
+    for c in atom.chars() {
+        if !(c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            selector.push('\\');
+        }
+        selector.push(c);
+    }
+    selector
+}
+
+/// Scopes making up the path a selector matches. They aren't exposed by syntect, but are serialized.
+fn selector_path(selector: &ScopeSelector) -> Vec<String> {
+    serde_json::to_value(selector).ok()
+        .and_then(|mut value| serde_json::from_value(value["path"]["scopes"].take()).ok())
+        .unwrap_or_default()
+}
+
+/// Generates a style sheet coloring code highlighted with classes the same way the theme colors
+/// it inline, or None if there's no such theme. CSS has no equivalent of the scope selectors
+/// excluding scopes, so those are applied without their exclusions.
+pub fn stylesheet(name: &str) -> Option<String> {
+    let theme = THEMESET.themes.get(name)?;
+    let settings = &theme.settings;
+    let mut css = String::new();
+
+    let mut block = String::new();
+    if let Some(background) = settings.background {
+        block.push_str(&format!(" background-color: {};", accentuate(background, 0)));
+    }
+    if let Some(foreground) = settings.foreground {
+        block.push_str(&format!(" color: {};", accentuate(foreground, 0)));
+    }
+    css.push_str(&format!("pre.highlight {{{} }}\n", block));
+
+    let numbering = settings.gutter_foreground.or(settings.foreground).unwrap_or(Color::BLACK);
+    css.push_str(&format!(
+        "pre.highlight code::before, pre.highlight code > span.line-number::before, pre.highlight code > span.marker::before {{ color: {}; }}\n",
+        accentuate(numbering, 0)
+    ));
+
+    if let Some(background) = alternate_background(theme) {
+        css.push_str(&format!("pre.highlight code.alternate {{ background-color: {}; }}\n", background));
+    }
+    if let Some(background) = emphasis_background(theme) {
+        css.push_str(&format!("pre.highlight code.emphasized {{ background-color: {}; }}\n", background));
+    }
+
+    for item in &theme.scopes {
+        let selectors: Vec<String> = item.scope.selectors.iter()
+            .map(selector_path)
+            .filter(|path| !path.is_empty())
+            .map(|path| {
+                let path: Vec<String> = path.iter()
+                    .map(|scope| scope.split('.').map(class_selector).collect())
+                    .collect();
+                format!("pre.highlight {}", path.join(" "))
+            })
+            .collect();
+
+        let style = &item.style;
+        let mut rules = String::new();
+        if let Some(foreground) = style.foreground {
+            rules.push_str(&format!(" color: {};", accentuate(foreground, 0)));
+        }
+        if let Some(background) = style.background {
+            rules.push_str(&format!(" background-color: {};", accentuate(background, 0)));
+        }
+        if let Some(font_style) = style.font_style {
+            rules.push_str(match font_style.contains(FontStyle::BOLD) {
+                true => " font-weight: bold;",
+                false => " font-weight: normal;",
+            });
+            rules.push_str(match font_style.contains(FontStyle::ITALIC) {
+                true => " font-style: italic;",
+                false => " font-style: normal;",
+            });
+            if font_style.contains(FontStyle::UNDERLINE) {
+                rules.push_str(" text-decoration: underline;");
+            }
+        }
+
+        if !selectors.is_empty() && !rules.is_empty() {
+            css.push_str(&format!("{} {{{} }}\n", selectors.join(", "), rules));
+        }
+    }
+
+    Some(css)
+}
+
+/// Reads a string argument, treating anything other than a string as a mistake
+fn string_arg(args: &HashMap<String, Value>, name: &str) -> Result<Option<String>> {
+    match args.get(name) {
+        Some(value) => tera::from_value::<String>(value.clone())
+            .map(Some)
+            .map_err(|_| tera::Error::from(format!("{} parameter must be a string", name))),
+        None => Ok(None),
+    }
+}
+
+/// Picks the syntax named by `lang`, which can also be a file extension such as "rs".
+/// Without one the syntax is guessed from the name of the file, if the source came from
+/// one, and then from the first line of the source, such as a shebang.
+fn find_syntax(lang: Option<&str>, file: Option<&str>, source: &str) -> Result<&'static SyntaxReference> {
+    if let Some(lang) = lang {
+        return SYNTAXSET.find_syntax_by_token(lang)
+            .ok_or_else(|| tera::Error::from(format!("unknown syntax {:?}", lang)));
+    }
+
+    let by_name = file.and_then(|file| {
+        let path = Path::new(file);
+        let name = path.file_name()?.to_str()?;
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+
+        SYNTAXSET.find_syntax_by_extension(name)
+            .or_else(|| SYNTAXSET.find_syntax_by_extension(extension))
+    });
+
+    Ok(by_name
+        .or_else(|| SYNTAXSET.find_syntax_by_first_line(source))
+        .unwrap_or_else(|| SYNTAXSET.find_syntax_plain_text()))
+}
+
+/// Parses line ranges such as "10-25,40-42" into the numbers of every line they cover
+fn parse_lines(ranges: &str, total: usize) -> Result<BTreeSet<usize>> {
+    let mut lines = BTreeSet::new();
+
+    for range in ranges.split(',').map(str::trim).filter(|range| !range.is_empty()) {
+        let invalid = || tera::Error::from(format!("invalid line range {:?}, expected something like 10-25", range));
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (start.trim(), end.trim()),
+            None => (range, range),
+        };
+
+        let start: usize = start.parse().map_err(|_| invalid())?;
+        let end: usize = end.parse().map_err(|_| invalid())?;
+
+        if start == 0 || start > end {
+            return Err(invalid());
+        }
+
+        if end > total {
+            return Err(tera::Error::from(format!(
+                "line range {:?} goes past line {}, the last line of the source", range, total
+            )));
+        }
+
+        lines.extend(start..=end);
+    }
+
+    match lines.is_empty() {
+        true => Err(tera::Error::from(format!("no line ranges found in {:?}", ranges))),
+        false => Ok(lines),
+    }
+}
+
+/// Parses callouts such as "3: Opens the connection" into the line they're attached to and their note
+fn parse_callout(callout: &str) -> Result<(usize, &str)> {
+    callout.split_once(':')
+        .and_then(|(line, note)| Some((line.trim().parse().ok()?, note.trim())))
+        .ok_or_else(|| tera::Error::from(format!(
+            "invalid callout {:?}, expected a line number followed by a note, such as \"3: Opens the connection\"",
+            callout
+        )))
+}
+
+/// Finds the lines between every `ANCHOR: name` and `ANCHOR_END: name` marker for the region.
+/// A region without an end marker runs until the end of the source.
+fn find_region(source: &str, region: &str) -> Result<BTreeSet<usize>> {
+    let mut lines = BTreeSet::new();
+    let mut inside = false;
+    let mut found = false;
+
+    for (number, line) in (1..).zip(source.lines()) {
+        match ANCHOR.captures(line) {
+            Some(marker) if &marker[2] == region => {
+                inside = marker.get(1).is_none();
+                found = true;
+            }
+            _ if inside => {
+                lines.insert(number);
+            }
+            _ => (),
+        }
+    }
+
+    match found {
+        true => Ok(lines),
+        false => Err(tera::Error::from(format!("no region named {:?} found in the source", region))),
+    }
+}
+
+/// Reads a source file, whether it's to be highlighted on its own or compared to another
+fn read_source(filename: &str) -> Result<String> {
+    fs::read_to_string(filename)
+        .map_err(|e| tera::Error::from(
+            format!("failed to generate syntax highlighting for {}: {}", filename, e)
+        ))
+}
+
+/// Styling for the line numbers of a block, which take their color from the theme
+fn gutter_style(theme: &Theme) -> String {
     let numbering = theme.settings.gutter_foreground
         .or(theme.settings.foreground)
         .unwrap_or(Color::BLACK);
 
-    output.push_str(&format!("<style type=\"text/css\">pre code::before {{ color: rgba({}, {}, {}, {}); }}</style>",
+    format!("<style type=\"text/css\">pre code::before, pre code > span.line-number::before, pre code > span.marker::before {{ color: rgba({}, {}, {}, {}); }}</style>",
         numbering.r, numbering.g, numbering.b, numbering.a
-    ));
+    )
+}
 
-    // This is an alternate background color used for every second line to distinguish them
-    let extras = theme.settings.background.map(|c|
-        format!("style=\"background-color: {};\"", accentuate(c, 5))
-    ).unwrap_or("".into());
+/// Which lines of the source are shown, and which of them are marked for the reader,
+/// all numbered as they are in the source
+#[derive(Default)]
+struct Layout {
+    /// Every line is shown unless only some of them are picked
+    shown: Option<BTreeSet<usize>>,
+    emphasized: BTreeSet<usize>,
+    /// Numbers of the callouts attached to each line, counting from 1
+    callouts: BTreeMap<usize, Vec<usize>>,
+}
+
+impl Layout {
+    fn is_shown(&self, line: usize) -> bool {
+        self.shown.as_ref().map(|shown| shown.contains(&line)).unwrap_or(true)
+    }
+}
+
+// This function is lifted more or less one-to-one from syntect, but adds the <code></code>
+// which allows us to do nice line numbering on em.
+//
+// Only the lines shown by the layout are included, numbered as they are in the source.
+// Lines left out are still highlighted, since the ones following them may depend on them,
+// such as when a region starts inside a comment. Anchor markers are always left out,
+// and every other gap is marked by an ellipsis, including those at either end.
+fn syntax_highlighter(source: &str, syntax: &SyntaxReference, colors: Colors, layout: &Layout) -> String {
+    let mut highlighter = LineHighlighter::new(syntax, colors);
+    let mut output = start_block(colors, None);
+    let (extras, emphasis) = line_attributes(colors);
 
-    let mut line = String::new();
     let mut alternate = false;
-    while highlighter.reader.read_line(&mut line)? > 0 {
-        {
-            if alternate {
-                output.push_str(&format!("<code {}>", extras));
-            } else {
-                output.push_str("<code>");
+    let mut started = false;
+    let mut skipped = false;
+    for (number, line) in (1..).zip(LinesWithEndings::from(source)) {
+        // Every line is highlighted, even when it isn't shown, to keep track of the syntax
+        let mut highlighted = String::new();
+        highlighter.append(line, &mut highlighted);
+
+        if ANCHOR.is_match(line) {
+            continue;
+        }
+
+        if !layout.is_shown(number) {
+            skipped = true;
+            continue;
+        }
+
+        if skipped {
+            output.push_str("<code class=\"ellipsis\">&#8943;\n</code>");
+        }
+        started = true;
+        skipped = false;
+
+        if layout.emphasized.contains(&number) {
+            output.push_str(&format!("<code data-line=\"{}\" {}>", number, emphasis));
+        } else if alternate {
+            output.push_str(&format!("<code data-line=\"{}\" {}>", number, extras));
+        } else {
+            output.push_str(&format!("<code data-line=\"{}\">", number));
+        }
+        alternate = !alternate;
+
+        // The line break goes after the callouts, so they end up on the same line
+        output.push_str(&highlighted);
+        for callout in layout.callouts.get(&number).into_iter().flatten() {
+            output.push_str(&format!("<span class=\"callout\">{}</span>", callout));
+        }
+        output.push_str("\n</code>");
+    }
+
+    if started && skipped {
+        output.push_str("<code class=\"ellipsis\">&#8943;\n</code>");
+    }
+    output.push_str("</pre>\n");
+    output
+}
+
+/// Lines are expected to end in a line break, which the last line of a file might not
+fn with_line_break(text: &str) -> Cow<'_, str> {
+    match text.ends_with('\n') {
+        true => Cow::Borrowed(text),
+        false => Cow::Owned(format!("{}\n", text)),
+    }
+}
+
+/// One version of the file in a diff, highlighted line by line. When the entire file is at hand,
+/// the lines between hunks are highlighted as well and thrown away, so that hunks starting inside
+/// a comment or a string are still highlighted correctly.
+struct DiffSide<'a> {
+    highlighter: LineHighlighter<'a>,
+    source: Vec<&'a str>,
+    /// Number of the next line of the source to be highlighted
+    next: usize,
+}
+
+impl<'a> DiffSide<'a> {
+    fn new(syntax: &'a SyntaxReference, colors: Colors, source: Option<&'a str>) -> Self {
+        DiffSide {
+            highlighter: LineHighlighter::new(syntax, colors),
+            source: source.map(|source| LinesWithEndings::from(source).collect()).unwrap_or_default(),
+            next: 1,
+        }
+    }
+
+    fn append(&mut self, number: usize, text: &str, output: &mut String) {
+        let mut skipped = String::new();
+        while self.next < number {
+            if let Some(line) = self.source.get(self.next - 1) {
+                self.highlighter.append(&with_line_break(line), &mut skipped);
+                skipped.clear();
             }
-            alternate = !alternate;
+            self.next += 1;
+        }
 
-            let regions = highlighter.highlight_lines.highlight(&line, &SYNTAXSET);
-            append_highlighted_html_for_styled_line(&regions[..], IncludeBackground::IfDifferent(bg), &mut output);
-            output.push_str("</code>");
+        self.highlighter.append(&with_line_break(text), output);
+        self.next = number + 1;
+    }
+}
+
+/// Renders the hunks of a diff with the old and new line numbers side by side, and a marker in
+/// front of every added or removed line. The old and new versions are highlighted separately,
+/// so removed lines are highlighted the way they were before the change, and added lines the
+/// way they are after it. Gaps between hunks are marked by an ellipsis. Given the `sources`
+/// before and after the change, the lines in the gaps are kept track of while highlighting.
+fn diff_highlighter(
+    hunks: &[Vec<DiffLine<'_>>],
+    syntax: &SyntaxReference,
+    colors: Colors,
+    sources: Option<(&str, &str)>,
+) -> String {
+    let mut old = DiffSide::new(syntax, colors, sources.map(|(old, _)| old));
+    let mut new = DiffSide::new(syntax, colors, sources.map(|(_, new)| new));
+    let mut output = start_block(colors, Some("diff"));
+
+    let number = |number: Option<usize>| number.map(|number| number.to_string()).unwrap_or_default();
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        if index > 0 {
+            output.push_str("<code class=\"ellipsis\">&#8943;\n</code>");
+        }
+
+        for line in hunk {
+            let (class, marker) = match line.change {
+                Change::Context => ("context", " "),
+                Change::Added => ("added", "+"),
+                Change::Removed => ("removed", "-"),
+            };
+
+            output.push_str(&format!(
+                "<code class=\"{}\"><span class=\"line-number\" data-line=\"{}\"></span><span class=\"line-number\" data-line=\"{}\"></span><span class=\"marker\" data-marker=\"{}\"></span>",
+                class, number(line.old), number(line.new), marker
+            ));
+
+            if let Some(number) = line.old {
+                let mut removed = String::new();
+                old.append(number, line.text, &mut removed);
+                if line.change == Change::Removed {
+                    output.push_str(&removed);
+                }
+            }
+
+            if let Some(number) = line.new {
+                new.append(number, line.text, &mut output);
+            }
+            output.push_str("\n</code>");
         }
-        line.clear();
     }
+
     output.push_str("</pre>\n");
-    Ok(output)
+    output
 }
 
+/// Highlights the changes in either a unified `diff`, or between the files `before` and `after`
+fn highlight_diff(args: &HashMap<String, Value>, lang: Option<&str>, colors: Colors) -> Result<Value> {
+    let unsupported = ["file", "text", "lines", "region", "emphasize", "callouts"].iter()
+        .find(|name| args.contains_key(**name));
 
-pub fn highlight(args: HashMap<String, Value>) -> Result<Value>{
-    // Extracting the chosen theme is split into two blocks like this
-    // to prevent heap-allocating the default theme string each time
-    let chosen_theme = match args.get("theme") {
-        Some(value) => tera::from_value::<String>(value.clone())
-            .map(|x| Some(x))
-            .unwrap_or(None),
-        _ => None
+    if let Some(name) = unsupported {
+        return Err(tera::Error::from(format!("the {} parameter can't be combined with a diff", name)));
+    }
+
+    let html = match (string_arg(args, "diff")?, string_arg(args, "before")?, string_arg(args, "after")?) {
+        (Some(filename), None, None) => {
+            let text = read_source(&filename)?;
+            let diff = diff::parse(&text).map_err(|e| tera::Error::from(format!("{}: {}", filename, e)))?;
+
+            // The first line of the file can only be used for guessing if the diff includes it
+            let first = diff.hunks[0].first()
+                .filter(|line| line.new == Some(1))
+                .map_or("", |line| line.text);
+
+            let syntax = find_syntax(lang, diff.path, first)?;
+            diff_highlighter(&diff.hunks, syntax, colors, None)
+        }
+        (None, Some(before), Some(after)) => {
+            let (old, new) = (read_source(&before)?, read_source(&after)?);
+            let syntax = find_syntax(lang, Some(&after), &new)?;
+            diff_highlighter(&diff::compare(&old, &new), syntax, colors, Some((&old, &new)))
+        }
+        (None, _, _) => return Err(tera::Error::from("expected both a before and an after parameter")),
+        (Some(_), _, _) => return Err(tera::Error::from("expected either a diff parameter or before and after parameters, not both")),
+    };
+
+    Ok(tera::to_value(html)?)
+}
+
+
+/// Highlights either a `file` or a snippet of `text`, guessing the syntax unless it's given
+/// as `lang` or `syntax`. Only part of the source is shown when given either `lines`, such as
+/// "10-25,40-42", or the name of a `region` marked by anchors in the source.
+///
+/// Lines can be pointed out with `emphasize`, which takes the same ranges as `lines`, and with
+/// `callouts`, a list of notes such as "3: Opens the connection" which are numbered in order
+/// and listed below the source. Notes are included as they are, so they may contain HTML.
+///
+/// Instead of a single source, the changes in a unified `diff` can be highlighted, or
+/// the changes between the files `before` and `after`, in the syntax of the changed file.
+///
+/// Code is colored as configured, unless given a `theme`, in which case the block is colored
+/// inline using that theme regardless, since the style sheets apply to every block alike.
+pub fn highlight(settings: &Highlighting, args: HashMap<String, Value>) -> Result<Value>{
+    let colors = match string_arg(&args, "theme")? {
+        Some(theme) => Colors::Inline(find_theme(&theme)?),
+        None if settings.classes => Colors::Classes,
+        None => Colors::Inline(find_theme(&settings.theme)?),
+    };
+
+    // The syntax is guessed unless named explicitly, by either of the two names
+    let lang = match string_arg(&args, "lang")? {
+        Some(lang) => Some(lang),
+        None => string_arg(&args, "syntax")?,
+    };
+
+    if ["diff", "before", "after"].iter().any(|name| args.contains_key(*name)) {
+        return highlight_diff(&args, lang.as_deref(), colors);
+    }
+
+    // The source is either read from a file, or given directly as text. Snippets written
+    // inside an article tend to start and end on lines of their own, so the line breaks
+    // surrounding the text are dropped, while indentation is left as it is.
+    let file = string_arg(&args, "file")?;
+    let source = match (&file, string_arg(&args, "text")?) {
+        (Some(filename), None) => read_source(filename)?,
+        (None, Some(text)) => text.trim_matches(['\r', '\n']).to_string() + "\n",
+        (Some(_), Some(_)) => return Err(tera::Error::from("expected either a file or a text parameter, not both")),
+        (None, None) => return Err(tera::Error::from("missing file or text parameter")),
+    };
+
+    // Parts of the source can be picked either by line numbers or by a named region
+    let total = source.lines().count();
+    let shown = match (string_arg(&args, "lines")?, string_arg(&args, "region")?) {
+        (Some(lines), None) => Some(parse_lines(&lines, total)?),
+        (None, Some(region)) => Some(find_region(&source, &region)?),
+        (Some(_), Some(_)) => return Err(tera::Error::from("expected either a lines or a region parameter, not both")),
+        (None, None) => None,
+    };
+
+    let mut layout = Layout {
+        shown,
+        ..Layout::default()
     };
 
-    let theme = match &chosen_theme {
-        Some(theme) => &THEMESET.themes[theme],
-        None => &THEMESET.themes[DEFAULT_THEME]
+    if let Some(emphasize) = string_arg(&args, "emphasize")? {
+        layout.emphasized = parse_lines(&emphasize, total)?;
+    }
+
+    let callouts = match args.get("callouts") {
+        Some(value) => tera::from_value::<Vec<String>>(value.clone())
+            .map_err(|_| tera::Error::from("callouts parameter must be a list of strings"))?,
+        None => Vec::new(),
     };
 
+    let mut notes = Vec::with_capacity(callouts.len());
+    for (number, callout) in (1..).zip(&callouts) {
+        let (line, note) = parse_callout(callout)?;
+        if line == 0 || line > total {
+            return Err(tera::Error::from(format!("callout {:?} points at a line outside of the source", callout)));
+        }
+
+        layout.callouts.entry(line).or_default().push(number);
+        notes.push(note);
+    }
+
+    // Marking lines which are left out would most likely be a mistake, and anchor markers are
+    // always left out, so the marks would silently go missing from the block otherwise
+    let lines: Vec<&str> = source.lines().collect();
+    let hidden = layout.emphasized.iter()
+        .chain(layout.callouts.keys())
+        .find(|&&line| !layout.is_shown(line) || ANCHOR.is_match(lines[line - 1]));
+
+    if let Some(line) = hidden {
+        return Err(tera::Error::from(format!("line {} is pointed out, but isn't shown", line)));
+    }
 
-    if let Some(value) = args.get("file") {
-        if let Ok(filename) = tera::from_value::<String>(value.clone()) { 
-            let html = syntax_highlighter(&filename, &theme)
-                .map_err(|e| tera::Error::from(
-                    format!("failed to generate syntax highlighting for {}: {}", &filename, e)
-                ))?;
+    let syntax = find_syntax(lang.as_deref(), file.as_deref(), &source)?;
+    let mut html = syntax_highlighter(&source, syntax, colors, &layout);
 
-            return Ok(tera::to_value(html)?);
+    if !notes.is_empty() {
+        html.push_str("<ol class=\"callouts\">");
+        for note in notes {
+            html.push_str(&format!("<li>{}</li>", note));
         }
+        html.push_str("</ol>\n");
     }
 
-    Err(tera::Error::from(format!("missing file or text parameter")))
+    Ok(tera::to_value(html)?)
 }
 
 pub fn codeblock(value: Value, _: HashMap<String, Value>) -> Result<Value> {
@@ -109,4 +816,183 @@ pub fn codeblock(value: Value, _: HashMap<String, Value>) -> Result<Value> {
     }
 
     Err(tera::Error::from(format!("missing input to codeblock function")))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(lines: &[usize]) -> BTreeSet<usize> {
+        lines.iter().cloned().collect()
+    }
+
+    #[test]
+    fn parses_line_ranges() {
+        assert_eq!(parse_lines("2-4, 7", 10).unwrap(), set(&[2, 3, 4, 7]));
+        assert_eq!(parse_lines("3-3,1-2", 10).unwrap(), set(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn rejects_invalid_line_ranges() {
+        assert!(parse_lines("4-2", 10).is_err());
+        assert!(parse_lines("0-3", 10).is_err());
+        assert!(parse_lines("0", 10).is_err());
+        assert!(parse_lines("9-11", 10).is_err());
+        assert!(parse_lines("a-b", 10).is_err());
+        assert!(parse_lines(" , ", 10).is_err());
+    }
+
+    #[test]
+    fn parses_callouts() {
+        assert_eq!(parse_callout("3: Opens the connection").unwrap(), (3, "Opens the connection"));
+        assert_eq!(parse_callout(" 12 :a: b ").unwrap(), (12, "a: b"));
+        assert!(parse_callout("Opens the connection").is_err());
+        assert!(parse_callout("three: Opens the connection").is_err());
+    }
+
+    #[test]
+    fn finds_regions_between_anchors() {
+        let source = "fn main() {\n    // ANCHOR: body\n    let x = 1;\n    // ANCHOR_END: body\n}\n";
+        assert_eq!(find_region(source, "body").unwrap(), set(&[3]));
+        assert!(find_region(source, "missing").is_err());
+    }
+
+    #[test]
+    fn regions_without_an_end_run_to_the_end() {
+        let source = "# ANCHOR: rest\nfirst\nsecond\n";
+        assert_eq!(find_region(source, "rest").unwrap(), set(&[2, 3]));
+    }
+
+    #[test]
+    fn anchors_must_be_comments_of_their_own() {
+        let source = "<!-- ANCHOR: a -->\nstatic ref ANCHOR: Regex\n/* ANCHOR_END: a */\n";
+        assert_eq!(find_region(source, "a").unwrap(), set(&[2]));
+
+        let html = syntax_highlighter(source, SYNTAXSET.find_syntax_plain_text(), Colors::Classes, &Layout::default());
+        assert!(html.contains("static ref ANCHOR: Regex"));
+        assert!(!html.contains("ANCHOR_END"));
+    }
+
+    fn highlight_text(text: &str, extra: &[(&str, Value)]) -> Result<String> {
+        let settings = Highlighting { classes: true, ..Highlighting::default() };
+        let mut args: HashMap<String, Value> = extra.iter().map(|(name, value)| (name.to_string(), value.clone())).collect();
+        args.insert("text".into(), Value::from(text));
+        args.insert("lang".into(), Value::from("rs"));
+
+        highlight(&settings, args).map(|html| html.as_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn lines_are_emphasized_and_called_out() {
+        let html = highlight_text("let a = 1;\nlet b = 2;\nlet c = 3;\n", &[
+            ("emphasize", Value::from("2")),
+            ("callouts", Value::from(vec!["1: First <em>note</em>", "3: Second", "1: Third"])),
+        ]).unwrap();
+
+        assert!(html.contains("<code data-line=\"2\" class=\"emphasized\">"));
+        assert!(html.contains("<code data-line=\"3\">"));
+        assert!(html.contains("<span class=\"callout\">1</span><span class=\"callout\">3</span>\n</code>"));
+        assert!(html.contains("<span class=\"callout\">2</span>\n</code>"));
+        assert!(html.ends_with("</pre>\n<ol class=\"callouts\"><li>First <em>note</em></li><li>Second</li><li>Third</li></ol>\n"));
+    }
+
+    #[test]
+    fn lines_left_out_cant_be_pointed_out() {
+        let source = "// ANCHOR: a\nlet a = 1;\n// ANCHOR_END: a\nlet b = 2;\n";
+        let region = ("region", Value::from("a"));
+
+        assert!(highlight_text(source, &[region.clone(), ("emphasize", Value::from("2"))]).is_ok());
+        assert!(highlight_text(source, &[region.clone(), ("emphasize", Value::from("4"))]).is_err());
+        assert!(highlight_text(source, &[("emphasize", Value::from("1"))]).is_err());
+        assert!(highlight_text(source, &[("callouts", Value::from(vec!["3: End of region"]))]).is_err());
+        assert!(highlight_text(source, &[("callouts", Value::from(vec!["5: Past the end"]))]).is_err());
+    }
+
+    #[test]
+    fn lines_between_hunks_are_highlighted_when_comparing() {
+        let before = format!("/*\n{}old\n*/\n", "comment\n".repeat(10));
+        let after = format!("/*\n{}new\n*/\n", "comment\n".repeat(10));
+        let syntax = SYNTAXSET.find_syntax_by_extension("rs").unwrap();
+
+        let hunks = diff::compare(&before, &after);
+        let html = diff_highlighter(&hunks, syntax, Colors::Classes, Some((&before, &after)));
+        assert!(html.contains("hl-comment hl-block hl-rust\">new"));
+        assert!(html.contains("hl-comment hl-block hl-rust\">old"));
+    }
+
+    #[test]
+    fn class_selectors_are_escaped() {
+        assert_eq!(class_selector("rust"), ".hl-rust");
+        assert_eq!(class_selector("c++"), ".hl-c\\+\\+");
+        assert_eq!(class_selector("objective-c_2"), ".hl-objective-c_2");
+    }
+
+    #[test]
+    fn stylesheets_color_blocks_like_their_theme() {
+        let css = stylesheet("Solarized (dark)").unwrap();
+        let lines: Vec<&str> = css.lines().collect();
+
+        assert_eq!(lines[0], "pre.highlight { background-color: rgba(0, 43, 54, 1.00); color: rgba(131, 148, 150, 1.00); }");
+        assert!(lines.contains(&"pre.highlight code.alternate { background-color: rgba(0, 38, 49, 1.00); }"));
+        assert!(lines.contains(&"pre.highlight code.emphasized { background-color: rgba(28, 209, 255, 0.07); }"));
+        assert!(lines.contains(&"pre.highlight .hl-storage.hl-modifier.hl-c\\+\\+ { color: rgba(133, 153, 0, 1.00); }"));
+        assert!(lines.contains(&"pre.highlight .hl-comment, pre.highlight .hl-meta.hl-documentation { color: rgba(88, 110, 117, 1.00); }"));
+
+        assert!(stylesheet("Solarized").is_none());
+    }
+
+    /// Color of every visible character of text in the HTML, as given by the innermost span
+    fn colors_of(html: &str) -> Vec<(char, String)> {
+        let mut colors = Vec::new();
+        let mut stack: Vec<Option<String>> = Vec::new();
+        let mut rest = html;
+
+        while let Some(c) = rest.chars().next() {
+            if c == '<' {
+                let end = rest.find('>').unwrap();
+                let tag = &rest[..=end];
+                if tag.starts_with("</span") {
+                    stack.pop();
+                } else if tag.starts_with("<span") {
+                    stack.push(tag.split("color:").nth(1).map(|color| color[..7].to_string()));
+                }
+                rest = &rest[end + 1..];
+                continue;
+            }
+
+            let color = stack.iter().rev().find_map(|color| color.as_ref());
+            if let (false, Some(color)) = (c.is_whitespace(), color) {
+                colors.push((c, color.clone()));
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+
+        colors
+    }
+
+    #[test]
+    fn inline_colors_match_highlighting_inline() {
+        let source = "/// Docs\nfn main() {\n    let x: u8 = \"text\".len() as u8; // note\n}\n";
+        let syntax = SYNTAXSET.find_syntax_by_extension("rs").unwrap();
+
+        for name in themes() {
+            let inline = syntax_highlighter(source, syntax, Colors::Inline(&THEMESET.themes[name]), &Layout::default());
+            let classes = syntax_highlighter(source, syntax, Colors::Classes, &Layout::default());
+
+            let converted = inline_colors(&classes, name);
+            assert!(!converted.contains(CLASS_PREFIX));
+            assert_eq!(colors_of(&converted), colors_of(&inline), "{}", name);
+        }
+    }
+
+    #[test]
+    fn skipped_lines_are_marked_at_either_end() {
+        let layout = Layout { shown: Some(set(&[2])), ..Layout::default() };
+        let html = syntax_highlighter("one\ntwo\nthree\n", SYNTAXSET.find_syntax_plain_text(), Colors::Classes, &layout);
+        assert_eq!(html.matches("class=\"ellipsis\"").count(), 2);
+
+        let layout = Layout { shown: Some(set(&[1, 2, 3])), ..Layout::default() };
+        let html = syntax_highlighter("one\ntwo\nthree\n", SYNTAXSET.find_syntax_plain_text(), Colors::Classes, &layout);
+        assert!(!html.contains("ellipsis"));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use actix::prelude::*;
+use actix_web_actors::ws;
+use actix_web::{HttpRequest, HttpResponse, Error, web::{Payload, Data}};
+use crate::site::Reloaded;
+use crate::stats::{HEARTBEAT_INTERVAL, CLIENT_TIMEOUT};
+
+/// Entry point for our route, only registered when live reloading is enabled
+pub fn live_reload(req: HttpRequest, stream: Payload, srv: Data<Addr<LiveReloadServer>>)
+    -> Result<HttpResponse, Error>
+{
+    ws::start(
+        LiveReloadSession {
+            id: 0,
+            hb: Instant::now(),
+            addr: srv.get_ref().clone(),
+        },
+        &req,
+        stream,
+    )
+}
+
+/// Live reloading is meant for authors previewing their articles,
+/// so it has to be explicitly enabled through the environment
+pub fn enabled() -> bool {
+    std::env::var("BLOG_LIVE_RELOAD").is_ok()
+}
+
+#[derive(Message, Clone)]
+pub struct Reload;
+
+pub struct Connect {
+    pub addr: Recipient<Reload>
+}
+
+impl Message for Connect {
+    type Result = usize;
+}
+
+#[derive(Message)]
+pub struct Disconnect {
+    pub id: usize
+}
+
+/// Keeps track of every open page, and tells them all to refresh
+/// whenever a new version of the site has been loaded
+#[derive(Default)]
+pub struct LiveReloadServer {
+    sessions: HashMap<usize, Recipient<Reload>>,
+    counter: usize
+}
+
+impl Actor for LiveReloadServer {
+    type Context = Context<Self>;
+}
+
+impl Handler<Reloaded> for LiveReloadServer {
+    type Result = ();
+
+    fn handle(&mut self, _: Reloaded, _: &mut Context<Self>) {
+        for session in self.sessions.values() {
+            // A session which has gone away will be cleaned up by its own Disconnect
+            let _ = session.do_send(Reload);
+        }
+    }
+}
+
+impl Handler<Connect> for LiveReloadServer {
+    type Result = usize;
+
+    fn handle(&mut self, msg: Connect, _: &mut Context<Self>) -> Self::Result {
+        let id = self.counter;
+        self.counter += 1;
+        self.sessions.insert(id, msg.addr);
+        id
+    }
+}
+
+impl Handler<Disconnect> for LiveReloadServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
+        self.sessions.remove(&msg.id);
+    }
+}
+
+pub struct LiveReloadSession {
+    id: usize,
+    hb: Instant,
+    addr: Addr<LiveReloadServer>
+}
+
+impl LiveReloadSession {
+    fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+                act.addr.do_send(Disconnect { id: act.id });
+                ctx.stop();
+                return;
+            }
+
+            ctx.ping("");
+        });
+    }
+}
+
+impl Actor for LiveReloadSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.hb(ctx);
+
+        let addr = ctx.address();
+        self.addr
+            .send(Connect {
+                addr: addr.recipient(),
+            })
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(res) => act.id = res,
+                    _ => ctx.stop(),
+                }
+                fut::ok(())
+            })
+            .wait(ctx);
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        self.addr.do_send(Disconnect { id: self.id });
+        Running::Stop
+    }
+}
+
+impl Handler<Reload> for LiveReloadSession {
+    type Result = ();
+
+    fn handle(&mut self, _: Reload, ctx: &mut Self::Context) {
+        ctx.text("reload")
+    }
+}
+
+impl StreamHandler<ws::Message, ws::ProtocolError> for LiveReloadSession {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Self::Context) {
+        match msg {
+            ws::Message::Ping(msg) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            ws::Message::Pong(_) => {
+                self.hb = Instant::now();
+            }
+            ws::Message::Nop => (),
+            _ => ctx.stop()
+        }
+    }
+}
@@ -6,18 +6,40 @@ extern crate serde;
 extern crate lazy_static;
 
 mod error;
+mod config;
 mod model;
 mod highlighter;
+mod diff;
 mod stats;
 mod nonce;
+mod markdown;
+mod analysis;
+mod related;
+mod search;
+mod content;
+mod site;
+mod reload;
+mod livereload;
+mod scheduler;
+mod pagination;
+mod archive;
+mod validation;
+mod feed;
+mod sitemap;
+mod pages;
+mod export;
 
 use actix::{prelude::*, Actor};
-use actix_web::{web::{self, Data, Path}, App, HttpServer, HttpResponse, HttpRequest};
-use tera::{Tera, Context};
-use model::World;
+use actix_web::{web::{self, Data, Path, Query}, App, HttpServer, HttpResponse, HttpRequest};
+use tera::Context;
 use error::BlogError;
+use site::{Site, SiteState, STATIC_DIR};
 use stats::{StatisticsServer, system_stats, GetInitialValues};
 use nonce::{CSPNonce, NonceRetrieval};
+use livereload::{LiveReloadServer, live_reload};
+use scheduler::Publisher;
+use feed::{FeedFormat, FeedQuery};
+use validation::Report;
 
 fn create_context(req: HttpRequest) -> Context {
     let stats = req.app_data::<Addr<StatisticsServer>>().unwrap();
@@ -26,6 +48,9 @@ fn create_context(req: HttpRequest) -> Context {
     let mut ctx = tera::Context::new();
     ctx.insert("csp_nonce", &serde_json::to_value(nonce).unwrap());
     ctx.insert("websocket", &std::env::var("BLOG_WEBSOCKET").unwrap_or("ws://localhost:8080".into()));
+    ctx.insert("livereload", &req.app_data::<Addr<LiveReloadServer>>().is_some());
+    // Searching takes a server, so exported pages leave the link out
+    ctx.insert("searchable", &true);
 
     if let Ok(stats) = stats.send(GetInitialValues {}).wait() {
         if let Ok(values) = stats {
@@ -36,57 +61,320 @@ fn create_context(req: HttpRequest) -> Context {
     ctx
 }
 
-fn index(world: Data<World>, tera: Data<Tera>, req: HttpRequest) -> Result<HttpResponse, BlogError> {
-    let mut ctx = create_context(req);
-    ctx.insert("articles", &world.articles);
-    let body = tera.render("frontpage.tera", &ctx)?;
-    Ok(HttpResponse::Ok().content_type("text/html").body(body))
+fn html(body: String) -> HttpResponse {
+    HttpResponse::Ok().content_type("text/html").body(body)
 }
 
-fn single_article(world: Data<World>, tera: Data<Tera>, req: HttpRequest, slug: Path<String>) 
+fn redirect(target: &str) -> HttpResponse {
+    HttpResponse::MovedPermanently().header("Location", target).finish()
+}
+
+fn index(state: Data<SiteState>, req: HttpRequest) -> Result<HttpResponse, BlogError> {
+    Ok(html(pages::frontpage(&state.current(), create_context(req), 1)?))
+}
+
+/// Anything not matched by another route is either redirected or shown the frontpage
+fn fallback(state: Data<SiteState>, req: HttpRequest) -> Result<HttpResponse, BlogError> {
+    if let Some(target) = state.current().redirect(req.path()) {
+        return Ok(redirect(&target));
+    }
+
+    index(state, req)
+}
+
+fn page(state: Data<SiteState>, req: HttpRequest, page: Path<usize>) -> Result<HttpResponse, BlogError> {
+    Ok(html(pages::frontpage(&state.current(), create_context(req), page.into_inner())?))
+}
+
+fn archive(state: Data<SiteState>, req: HttpRequest) -> Result<HttpResponse, BlogError> {
+    Ok(html(pages::archive(&state.current(), create_context(req))?))
+}
+
+fn archive_year(state: Data<SiteState>, req: HttpRequest, path: Path<(i32,)>)
+    -> Result<HttpResponse, BlogError>
+{
+    Ok(html(pages::archive_year(&state.current(), create_context(req), path.0)?))
+}
+
+fn archive_month(state: Data<SiteState>, req: HttpRequest, path: Path<(i32, u32)>)
+    -> Result<HttpResponse, BlogError>
+{
+    let (year, month) = path.into_inner();
+    Ok(html(pages::archive_month(&state.current(), create_context(req), year, month)?))
+}
+
+/// Serves the article if it belongs at the requested path, which depends on the language it's
+/// written in. Otherwise it's redirected to wherever it does belong, if it exists at all.
+fn serve_article(state: Data<SiteState>, req: HttpRequest, url: &str, slug: &str)
+    -> Result<HttpResponse, BlogError>
+{
+    let site = state.current();
+
+    match site.world.find_by_slug(slug) {
+        Ok(article) if article.url == url => Ok(html(pages::article(&site, create_context(req), slug)?)),
+        _ => match site.redirect(req.path()) {
+            Some(target) => Ok(redirect(&target)),
+            None => Err(BlogError::MissingContent("no article with that name found".into())),
+        },
+    }
+}
+
+fn single_article(state: Data<SiteState>, req: HttpRequest, slug: Path<String>) 
     -> Result<HttpResponse, BlogError> 
 {
-    let article = world.find_by_slug(&slug)?;
-    let mut ctx = create_context(req);
-    ctx.insert("article", &article);
-    let body = tera.render("single-article.tera", &ctx)?;
+    serve_article(state, req, &format!("/articles/{}", slug), &slug)
+}
+
+fn translated_article(state: Data<SiteState>, req: HttpRequest, path: Path<(String, String)>)
+    -> Result<HttpResponse, BlogError>
+{
+    let (language, slug) = path.into_inner();
+    serve_article(state, req, &format!("/{}/articles/{}", language, slug), &slug)
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+fn search(state: Data<SiteState>, req: HttpRequest, query: Query<SearchQuery>)
+    -> Result<HttpResponse, BlogError>
+{
+    Ok(html(pages::search(&state.current(), create_context(req), &query.q)?))
+}
+
+fn search_json(state: Data<SiteState>, query: Query<SearchQuery>) -> HttpResponse {
+    let site = state.current();
+    let results = site.world.search.search(&site.world.articles, &query.q);
+    HttpResponse::Ok().json(results)
+}
+
+fn tags(state: Data<SiteState>, req: HttpRequest) -> Result<HttpResponse, BlogError> {
+    Ok(html(pages::tags(&state.current(), create_context(req))?))
+}
+
+fn single_tag(state: Data<SiteState>, req: HttpRequest, slug: Path<String>)
+    -> Result<HttpResponse, BlogError>
+{
+    Ok(html(pages::tag(&state.current(), create_context(req), &slug)?))
+}
+
+fn single_category(state: Data<SiteState>, req: HttpRequest, slug: Path<String>)
+    -> Result<HttpResponse, BlogError>
+{
+    Ok(html(pages::category(&state.current(), create_context(req), &slug)?))
+}
+
+fn single_series(state: Data<SiteState>, req: HttpRequest, slug: Path<String>)
+    -> Result<HttpResponse, BlogError>
+{
+    Ok(html(pages::series(&state.current(), create_context(req), &slug)?))
+}
+
+fn single_author(state: Data<SiteState>, req: HttpRequest, slug: Path<String>)
+    -> Result<HttpResponse, BlogError>
+{
+    Ok(html(pages::author(&state.current(), create_context(req), &slug)?))
+}
+
+fn feed(state: Data<SiteState>, query: Query<FeedQuery>, format: FeedFormat)
+    -> Result<HttpResponse, BlogError>
+{
+    let body = feed::render(&state.current(), format, query.author.as_deref())?;
+    Ok(HttpResponse::Ok().content_type(format.content_type()).body(body))
+}
+
+fn atom_feed(state: Data<SiteState>, query: Query<FeedQuery>) -> Result<HttpResponse, BlogError> {
+    feed(state, query, FeedFormat::Atom)
+}
+
+fn rss_feed(state: Data<SiteState>, query: Query<FeedQuery>) -> Result<HttpResponse, BlogError> {
+    feed(state, query, FeedFormat::Rss)
+}
+
+fn json_feed(state: Data<SiteState>, query: Query<FeedQuery>) -> Result<HttpResponse, BlogError> {
+    let body = feed::render_json(&state.current(), query.author.as_deref(), query.page.unwrap_or(1))?;
+    Ok(HttpResponse::Ok().content_type("application/feed+json").body(body))
+}
+
+fn sitemap(state: Data<SiteState>) -> Result<HttpResponse, BlogError> {
+    let body = sitemap::render_sitemap(&state.current())?;
+    Ok(HttpResponse::Ok().content_type("application/xml").body(body))
+}
+
+fn highlight_stylesheet(theme: Path<String>) -> Result<HttpResponse, BlogError> {
+    let css = highlighter::stylesheet(&theme)
+        .ok_or_else(|| BlogError::MissingContent("no theme with that name found".into()))?;
+
+    Ok(HttpResponse::Ok().content_type("text/css").body(css))
+}
 
-    Ok(HttpResponse::Ok().content_type("text/html").body(body))
+fn robots(state: Data<SiteState>) -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body(sitemap::render_robots(&state.current()))
+}
+
+/// Loads the site, or prints every problem found and exits if it can't be served
+fn load_or_exit() -> Site {
+    match Site::load() {
+        Ok(site) => site,
+        Err(e) => {
+            eprint!("failed to load templates and articles, {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Loads the site without serving it, printing every problem found.
+/// Exits with a non-zero status if any of them are errors.
+fn check() -> std::io::Result<()> {
+    let mut report = Report::default();
+    Site::build(&mut report);
+
+    if report.is_empty() {
+        println!("no problems found");
+        return Ok(());
+    }
+
+    print!("{}", report);
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Renders the whole site into `out` instead of serving it
+fn build(out: &str) -> std::io::Result<()> {
+    let site = load_or_exit();
+
+    match export::build(&site, out) {
+        Ok(written) => {
+            println!("wrote {} files to {}", written, out);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("failed to build site: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("build") => return build(args.get(2).map(String::as_str).unwrap_or("public")),
+        Some("check") => return check(),
+        Some(command) => {
+            eprintln!("unknown command {:?}, expected build, check or nothing at all", command);
+            std::process::exit(2);
+        }
+        None => (),
+    }
+
     let _sys = actix::System::new("system");
 
     let stats_server = StatisticsServer::default().start();
 
-    HttpServer::new(move || {
-        let mut tera = Tera::new("resources/templates/**/*")
-            .expect("failed to initialize templates");
-        
-        tera.register_function("highlight", Box::new(highlighter::highlight));
-        tera.register_filter("codeblock", highlighter::codeblock);
+    let site = load_or_exit();
+    let state = Data::new(SiteState::new(site));
+
+    // Kept around for the lifetime of the server, since dropping it stops the watcher
+    let _watcher = reload::watch(state.clone()).expect("failed to watch for changes");
+
+    let publisher = Publisher::new(state.clone()).start();
+    state.subscribe(publisher.recipient());
 
-        let world = World::new(&tera, include_str!("../resources/articles.json"));
+    let live_reload_server = if livereload::enabled() {
+        let server = LiveReloadServer::default().start();
+        state.subscribe(server.clone().recipient());
+        Some(server)
+    } else {
+        None
+    };
 
-        App::new()
+    HttpServer::new(move || {
+        let app = App::new()
             .data(stats_server.clone())
-            .data(world)
-            .data(tera)
+            .register_data(state.clone())
             .wrap(actix_web::middleware::Logger::default())
             .wrap(actix_web::middleware::Compress::default())
             .wrap(CSPNonce::default())
             .default_service(
-                web::resource("/").to(index)
+                web::resource("/").to(fallback)
+            )
+            // Generated style sheets must come before the static files, which would otherwise claim them
+            .service(
+                web::resource("/static/highlight/{theme}.css").to(highlight_stylesheet)
             )
             .service(
-                actix_files::Files::new("/static", "resources/static")
+                actix_files::Files::new("/static", STATIC_DIR)
             )
             .service(
                 web::resource("/statistics").to(system_stats)
             )
+            .service(
+                web::resource("/page/{page}").to(page)
+            )
+            .service(
+                web::resource(FeedFormat::Atom.path()).to(atom_feed)
+            )
+            .service(
+                web::resource(FeedFormat::Rss.path()).to(rss_feed)
+            )
+            .service(
+                web::resource(feed::JSON_FEED_PATH).to(json_feed)
+            )
+            .service(
+                web::resource(sitemap::SITEMAP_PATH).to(sitemap)
+            )
+            .service(
+                web::resource("/robots.txt").to(robots)
+            )
+            .service(
+                web::resource("/archive").to(archive)
+            )
+            .service(
+                web::resource("/archive/{year}").to(archive_year)
+            )
+            .service(
+                web::resource("/archive/{year}/{month}").to(archive_month)
+            )
             .service(
                 web::resource("/articles/{slug}").to(single_article)
             )
+            .service(
+                web::resource("/{language}/articles/{slug}").to(translated_article)
+            )
+            .service(
+                web::resource("/search").to(search)
+            )
+            .service(
+                web::resource("/search.json").to(search_json)
+            )
+            .service(
+                web::resource("/tags").to(tags)
+            )
+            .service(
+                web::resource("/tags/{slug}").to(single_tag)
+            )
+            .service(
+                web::resource("/categories/{slug}").to(single_category)
+            )
+            .service(
+                web::resource("/series/{slug}").to(single_series)
+            )
+            .service(
+                web::resource("/authors/{slug}").to(single_author)
+            );
+
+        match &live_reload_server {
+            Some(server) => app
+                .data(server.clone())
+                .service(web::resource("/livereload").to(live_reload)),
+            None => app
+        }
     })
     .bind("127.0.0.1:8080")?
     .run()
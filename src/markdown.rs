@@ -0,0 +1,19 @@
+use pulldown_cmark::{html, Options, Parser};
+
+/// Renders CommonMark (plus tables, footnotes and strikethrough) into HTML.
+///
+/// Articles are passed through tera before reaching this point, so any
+/// highlighted code blocks have already been expanded into raw HTML blocks
+/// which CommonMark leaves untouched.
+pub fn render(source: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let parser = Parser::new_ext(source, options);
+
+    let mut output = String::with_capacity(source.len());
+    html::push_html(&mut output, parser);
+    output
+}
@@ -1,78 +1,816 @@
-use crate::error::BlogError;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::analysis::{self, Heading};
+use crate::config::Config;
+use crate::content::{self, ArticleSource};
+use crate::error::{describe_tera_error, BlogError};
+use crate::markdown;
+use crate::related;
+use crate::search::SearchIndex;
+use crate::site::TEMPLATE_DIR;
+use crate::validation::{Problem, Report};
 use tera::{Context, Tera};
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
-pub struct Author<'a> {
-    pub name: &'a str,
-    pub email: Option<&'a str>,
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Link {
+    pub name: String,
+    pub url: String,
+}
+
+/// An entry in the authors registry, which articles refer to by id
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Author {
+    /// Taken from the key of the entry in the registry
+    #[serde(default)]
+    pub id: String,
+    /// Used for linking to the author's profile, defaults to the id
+    #[serde(default)]
+    pub slug: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub bio: Option<String>,
+    /// Path to the author's picture, such as /static/images/avatar.png
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub links: Vec<Link>,
+}
+
+/// A tag or category, along with the slug used to link to its listing page
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Taxonomy {
+    pub name: String,
+    pub slug: String,
+}
+
+impl Taxonomy {
+    pub fn new(name: &str) -> Result<Taxonomy, tera::Error> {
+        Ok(Taxonomy {
+            name: name.to_string(),
+            slug: slugify(name)?,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct Article<'a> {
-    pub title: &'a str,
-    pub author: Author<'a>,
+pub struct Article {
+    pub title: String,
+    pub authors: Vec<Author>,
     pub published: u64,
+    /// Time of the last significant change, the same as `published` unless specified
+    pub updated: u64,
     pub body: String,
+    /// Beginning of the body shown in listings, as balanced HTML
+    pub excerpt: String,
+    pub words: usize,
+    /// Estimated reading time in minutes
+    pub reading_time: usize,
+    /// Nested table of contents, built from the h2 through h4 headings of the body
+    pub toc: Vec<Heading>,
+    pub slug: String,
+    /// Path the article is served from, prefixed with its language unless it's the default one
+    pub url: String,
+    pub aliases: Vec<String>,
+    /// Language code, such as "en"
+    pub language: String,
+    /// Every other translation of the article, ordered by language code
+    pub translations: Vec<Translation>,
+    pub tags: Vec<Taxonomy>,
+    pub category: Option<Taxonomy>,
+    pub series: Option<SeriesNavigation>,
+    /// The most similar listed articles, most similar first
+    pub related: Vec<ArticleLink>,
+    pub visibility: Visibility,
+}
+
+impl Article {
+    /// Whether the article should show up on the frontpage, in feeds and other listings
+    pub fn is_listed(&self) -> bool {
+        self.visibility == Visibility::Public
+    }
+}
+
+/// Minimal reference to another article, for linking between them
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArticleLink {
+    pub title: String,
+    pub slug: String,
+    pub url: String,
+}
+
+impl<'a> From<&'a Article> for ArticleLink {
+    fn from(article: &'a Article) -> Self {
+        ArticleLink {
+            title: article.title.clone(),
+            slug: article.slug.clone(),
+            url: article.url.clone(),
+        }
+    }
+}
+
+/// Another language an article has been translated to, for linking between translations
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Translation {
+    pub language: String,
+    /// Name of the language, as it's written in the configuration
+    pub name: String,
+    pub title: String,
+    pub url: String,
+}
+
+/// Where an article is placed within its series, and links to the rest of the parts
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SeriesNavigation {
+    pub name: String,
     pub slug: String,
+    pub part: u32,
+    pub previous: Option<ArticleLink>,
+    pub next: Option<ArticleLink>,
+    pub parts: Vec<ArticleLink>,
 }
 
+/// Every article sharing a tag or category, referenced by their position in `World::articles`
+pub struct Group {
+    pub taxonomy: Taxonomy,
+    pub articles: Vec<usize>,
+}
+
+/// An author along with every article they have written or co-written,
+/// referenced by their position in `World::articles`
+pub struct Profile {
+    pub author: Author,
+    pub articles: Vec<usize>,
+}
+
+/// Summary of a group used for overview pages, where listing every article would be too much
+#[derive(Serialize)]
+pub struct GroupSummary<'a> {
+    pub name: &'a str,
+    pub slug: &'a str,
+    pub count: usize,
+}
+
+/// The markup language an article template is written in. Either way the template
+/// is rendered through tera first, so functions like `highlight` are available to both.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Listed everywhere
+    #[default]
+    Public,
+    /// Reachable through its slug, but left out of every listing
+    Unlisted,
+    /// Not served at all
+    Draft,
+    /// Public once the `published` timestamp has passed. Public or unlisted
+    /// articles with a `published` timestamp in the future are treated the same way.
+    Scheduled,
+}
+
+/// Front matter found at the top of each file in the content directory
 #[derive(Serialize, Deserialize)]
-pub struct ArticleMetaData<'a> {
-    pub title: &'a str,
-    pub author: Author<'a>,
+pub struct ArticleMetaData {
+    pub title: String,
+    /// Ids of the authors in the authors registry
+    pub authors: Vec<String>,
     pub published: u64,
-    pub template: &'a str,
-    pub slug: Option<&'a str>,
+    pub updated: Option<u64>,
+    /// Article template to use as the body, instead of the text following the front matter
+    pub template: Option<String>,
+    pub slug: Option<String>,
+    /// Language code of the article, defaults to the language of the site
+    pub language: Option<String>,
+    /// Key shared by every translation of the same article, such as the slug of the original
+    pub translation: Option<String>,
+    /// Slugs the article was previously published under, which are redirected to the current one
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub format: Option<Format>,
+    /// Shown in listings instead of the beginning of the body, in the same format as the body
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    /// Name of the series this article belongs to, numbered from 1 by `part`
+    pub series: Option<String>,
+    pub part: Option<u32>,
+    #[serde(default)]
+    pub visibility: Visibility,
+}
+
+impl ArticleMetaData {
+    /// Determines the visibility of the article at the given point in time,
+    /// or None if the article should not be served at all
+    pub fn visibility_at(&self, now: u64) -> Option<Visibility> {
+        match self.visibility {
+            Visibility::Draft => None,
+            _ if self.published > now => None,
+            Visibility::Scheduled => Some(Visibility::Public),
+            visibility => Some(visibility),
+        }
+    }
+}
+
+impl ArticleMetaData {
+    /// Uses the explicitly specified format if there is one, otherwise
+    /// guesses based on the file extension of the article's source
+    pub fn format(&self, source: &str) -> Format {
+        match self.format {
+            Some(format) => format,
+            None if source.ends_with(".md") || source.ends_with(".markdown") => Format::Markdown,
+            None => Format::Html,
+        }
+    }
 }
 
-pub struct World<'a> {
-    pub articles: Vec<Article<'a>>,
+pub struct World {
+    pub articles: Vec<Article>,
+    /// Tags and categories, keyed by their slugs
+    pub tags: BTreeMap<String, Group>,
+    pub categories: BTreeMap<String, Group>,
+    /// Series, keyed by their slugs, with articles ordered by part number
+    pub series: BTreeMap<String, Group>,
+    /// Authors, keyed by their slugs
+    pub authors: BTreeMap<String, Profile>,
+    /// Publishing time of the next scheduled article, if any
+    pub next_scheduled: Option<u64>,
+    /// Lowercased slugs and former slugs, referencing the articles they belong to
+    pub redirects: HashMap<String, usize>,
+    pub search: SearchIndex,
 }
 
-impl<'a> World<'a> {
-    pub fn new(tera: &tera::Tera, articles_json: &'a str) -> World<'a> {
-        let metadata: Vec<ArticleMetaData<'a>> =
-            serde_json::from_str(articles_json).expect("failed to load article metadata");
+/// Timestamps beyond the year 5000 are rejected, since they were most likely written in milliseconds
+const MAX_TIMESTAMP: u64 = 100_000_000_000;
 
-        let articles = metadata
-            .iter()
-            .map(|article| {
+/// Seconds since the unix epoch, the same unit used for `published`
+pub fn current_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
 
-                let body = tera
-                    .render(&format!("articles/{}", &article.template), &Context::new())
-                    .expect(&format!(
-                        "unable to load article template {}",
-                        &article.template
-                    ));
+/// Path an article is served from. Slugs are unique across languages, but articles not written
+/// in the default language get a prefix anyway, such as /da/articles/, to keep them apart.
+pub fn article_url(language: &str, default_language: &str, slug: &str) -> String {
+    match language == default_language {
+        true => format!("/articles/{}", slug),
+        false => format!("/{}/articles/{}", language, slug),
+    }
+}
 
-                // Unless a slug is explicitly specified, generate one using tera's slugify functionality
-                // based on the article's title
-                let slug = match article.slug {
-                    Some(slug) => slug.into(),
-                    None => {
-                        let mut ctx = Context::new();
-                        ctx.insert(&"title", &article.title);
-                        Tera::one_off("{{ title | slugify }}", &ctx, false).expect(&format!(
-                            "unable to generate slug for article {}",
-                            &article.title
-                        ))
+/// Slugs end up as directory names when the site is exported,
+/// so they're limited to what is safe to use in both paths and URLs
+fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty() && slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Generates a slug using tera's slugify functionality
+fn slugify(text: &str) -> Result<String, tera::Error> {
+    let mut ctx = Context::new();
+    ctx.insert("text", text);
+    Tera::one_off("{{ text | slugify }}", &ctx, false)
+}
+
+/// Adds the article at the given index to the group for the taxonomy, creating the group
+/// if necessary. Names which only differ in casing or punctuation share the same slug,
+/// in which case the first spelling encountered is the one displayed.
+fn add_to_group(groups: &mut BTreeMap<String, Group>, taxonomy: &Taxonomy, index: usize) {
+    groups
+        .entry(taxonomy.slug.clone())
+        .or_insert_with(|| Group {
+            taxonomy: taxonomy.clone(),
+            articles: Vec::new(),
+        })
+        .articles
+        .push(index);
+}
+
+/// A part of a series, along with the index of its article if it is visible.
+/// Drafts and parts scheduled for later count towards the numbering all the same.
+type SeriesPart<'a> = (u32, Option<usize>, &'a ArticleSource);
+
+/// Orders the parts of every series, making sure they are numbered 1 through N without
+/// gaps or duplicates, and then fills in the series navigation of every visible part.
+/// Series with problems in their numbering are reported and left out.
+fn link_series(
+    articles: &mut [Article],
+    memberships: BTreeMap<String, (Taxonomy, Vec<SeriesPart<'_>>)>,
+    report: &mut Report,
+) -> BTreeMap<String, Group> {
+    let mut series = BTreeMap::new();
+
+    'series: for (slug, (taxonomy, mut parts)) in memberships {
+        parts.sort_by(|(part, _, source), (other, _, other_source)| {
+            part.cmp(other).then_with(|| source.path.cmp(&other_source.path))
+        });
+
+        for (expected, &(part, _, source)) in (1..).zip(parts.iter()) {
+            let problem = if part == 0 {
+                format!("parts of series {:?} are numbered starting from 1", taxonomy.name)
+            } else if part < expected {
+                let (_, _, previous) = parts[(expected - 2) as usize];
+                format!(
+                    "duplicate part {} of series {:?}, already used by {}",
+                    part, taxonomy.name, previous.path.display()
+                )
+            } else if part > expected {
+                format!("series {:?} is missing part {}, found part {} instead", taxonomy.name, expected, part)
+            } else {
+                continue;
+            };
+
+            report.add(Problem::error(&source.path, source.line_of("part"), problem));
+            continue 'series;
+        }
+
+        // Hidden parts are left out of the navigation until they become visible
+        let parts: Vec<(u32, usize)> = parts.into_iter()
+            .filter_map(|(part, index, _)| index.map(|index| (part, index)))
+            .collect();
+
+        if parts.is_empty() {
+            continue;
+        }
+
+        let links: Vec<ArticleLink> = parts.iter()
+            .map(|&(_, index)| ArticleLink::from(&articles[index]))
+            .collect();
+
+        for (position, &(part, index)) in parts.iter().enumerate() {
+            articles[index].series = Some(SeriesNavigation {
+                name: taxonomy.name.clone(),
+                slug: taxonomy.slug.clone(),
+                part,
+                previous: position.checked_sub(1).map(|previous| links[previous].clone()),
+                next: links.get(position + 1).cloned(),
+                parts: links.clone(),
+            });
+        }
+
+        series.insert(slug, Group {
+            taxonomy,
+            articles: parts.into_iter().map(|(_, index)| index).collect(),
+        });
+    }
+
+    series
+}
+
+/// An article with a translation key and the language it is written in, along with the index
+/// of the article if it is visible. Hidden articles take up their language all the same.
+type TranslationPart<'a> = (String, Option<usize>, &'a ArticleSource);
+
+/// Links every visible article to its visible translations, which share the same translation key.
+/// Each language may only appear once per key, so translations with a language already taken by
+/// another are reported and left unlinked, even while either of them is still hidden.
+fn link_translations(
+    articles: &mut [Article],
+    groups: BTreeMap<String, Vec<TranslationPart<'_>>>,
+    config: &Config,
+    report: &mut Report,
+) {
+    for (key, parts) in groups {
+        let mut taken: HashMap<String, &ArticleSource> = HashMap::new();
+        let mut group: BTreeMap<String, usize> = BTreeMap::new();
+
+        for (language, index, source) in parts {
+            if let Some(other) = taken.get(&language) {
+                report.add(Problem::error(&source.path, source.line_of("translation"), format!(
+                    "translation {:?} already has an article in {:?}: {}",
+                    key, language, other.path.display()
+                )));
+                continue;
+            }
+
+            taken.insert(language.clone(), source);
+            if let Some(index) = index {
+                group.insert(language, index);
+            }
+        }
+
+        for &index in group.values() {
+            articles[index].translations = group.values()
+                .filter(|&&other| other != index)
+                .map(|&other| {
+                    let article = &articles[other];
+                    Translation {
+                        language: article.language.clone(),
+                        name: config.languages[&article.language].clone(),
+                        title: article.title.clone(),
+                        url: article.url.clone(),
+                    }
+                })
+                .collect();
+        }
+    }
+}
+
+impl World {
+    /// Discovers every article in the content directory and renders them using tera.
+    /// Articles without an explicit template have their body registered as a template
+    /// of its own, which is why this requires a mutable reference to tera.
+    /// Each article is given links to its translations, and to the
+    /// `related_articles` most similar articles as configured.
+    ///
+    /// Drafts and articles scheduled for later are still rendered to catch errors early,
+    /// but are left out of the world entirely until they become visible.
+    ///
+    /// Articles with problems are added to the report and left out, so that every
+    /// problem in the content directory can be found in a single pass.
+    pub fn new<P: AsRef<Path>>(
+        tera: &mut Tera,
+        content_dir: P,
+        registry: &BTreeMap<String, Author>,
+        config: &Config,
+        report: &mut Report,
+    ) -> World {
+        let content_dir = content_dir.as_ref();
+        let mut sources = content::discover(content_dir, report);
+        let now = current_time();
+
+        // Newest articles first, which every listing relies on. The sort is stable,
+        // so articles published at the same time keep the order of their file names
+        sources.sort_by_key(|source| Reverse(source.metadata.published));
+
+        let mut articles = Vec::with_capacity(sources.len());
+        let mut article_sources = Vec::with_capacity(sources.len());
+        let mut slugs: HashMap<String, &Path> = HashMap::new();
+        let mut memberships = BTreeMap::new();
+        let mut translations: BTreeMap<String, Vec<TranslationPart<'_>>> = BTreeMap::new();
+        let mut next_scheduled: Option<u64> = None;
+
+        for source in &sources {
+            let article = &source.metadata;
+            let error = |key: &str, message: String| Problem::error(&source.path, source.line_of(key), message);
+
+            let template = match &article.template {
+                Some(template) => {
+                    let name = format!("articles/{}", template);
+                    if tera.get_template(&name).is_err() {
+                        report.add(error("template", format!(
+                            "template {} does not exist in {}/articles", template, TEMPLATE_DIR
+                        )));
+                        continue;
                     }
+                    name
+                }
+                None => {
+                    let relative = source.path.strip_prefix(content_dir).unwrap_or(&source.path);
+                    let name = format!("content/{}", relative.display());
+                    if let Err(e) = tera.add_raw_template(&name, &source.body) {
+                        report.add(Problem::error(&source.path, None, describe_tera_error(&e)));
+                        continue;
+                    }
+                    name
+                }
+            };
+
+            let body = match tera.render(&template, &Context::new()) {
+                Ok(body) => body,
+                Err(e) => {
+                    report.add(Problem::error(&source.path, None, format!(
+                        "unable to render article template {}: {}", template, describe_tera_error(&e)
+                    )));
+                    continue;
+                }
+            };
+
+            let format = article.format(&template);
+            let body = match format {
+                Format::Markdown => markdown::render(&body),
+                Format::Html => body,
+            };
+
+            // Excerpts are taken before headings are given anchors, since
+            // those would point at the listing rather than the article
+            let excerpt = match (&article.summary, format) {
+                (Some(summary), Format::Markdown) => analysis::balance(&markdown::render(summary)),
+                (Some(summary), Format::Html) => analysis::balance(summary),
+                (None, _) => analysis::excerpt(&body),
+            };
+
+            let analysis = analysis::analyse(&body);
+
+            // Timestamps are in seconds, so anything this large was almost certainly
+            // meant as milliseconds, and would otherwise be scheduled for the far future
+            let mut valid = true;
+            for (key, timestamp) in &[("published", Some(article.published)), ("updated", article.updated)] {
+                if timestamp.map(|timestamp| timestamp > MAX_TIMESTAMP).unwrap_or(false) {
+                    report.add(error(key, format!(
+                        "{} timestamp is too large, expected seconds since the unix epoch", key
+                    )));
+                    valid = false;
+                }
+            }
+
+            if !valid {
+                continue;
+            }
+
+            if article.updated.map(|updated| updated < article.published).unwrap_or(false) {
+                report.add(Problem::warning(&source.path, source.line_of("updated"),
+                    "article was updated before it was published".into()
+                ));
+            }
+
+            // Unless a slug is explicitly specified, generate one using tera's slugify functionality
+            // based on the article's title
+            let slug = match &article.slug {
+                Some(slug) => slug.clone(),
+                None => match slugify(&article.title) {
+                    Ok(slug) => slug,
+                    Err(e) => {
+                        report.add(error("title", format!(
+                            "unable to generate slug for article {}: {}", &article.title, e
+                        )));
+                        continue;
+                    }
+                },
+            };
+
+            if !is_valid_slug(&slug) {
+                let key = if article.slug.is_some() { "slug" } else { "title" };
+                report.add(error(key, format!(
+                    "invalid slug {:?}, expected only lowercase letters, digits and dashes", slug
+                )));
+                continue;
+            }
+
+            if let Some(existing) = slugs.insert(slug.clone(), &source.path) {
+                let key = if article.slug.is_some() { "slug" } else { "title" };
+                report.add(error(key, format!(
+                    "duplicate slug {:?}, already used by {}", slug, existing.display()
+                )));
+                continue;
+            }
+
+            let language = article.language.clone().unwrap_or_else(|| config.language.clone());
+            if !config.languages.contains_key(&language) {
+                report.add(error("language", format!(
+                    "unknown language {:?}, expected one of the languages in the configuration", language
+                )));
+                continue;
+            }
+
+            if article.authors.is_empty() {
+                report.add(error("authors", "article has no authors".into()));
+                continue;
+            }
+
+            let unknown: Vec<&String> = article.authors.iter()
+                .filter(|id| !registry.contains_key(*id))
+                .collect();
+
+            if !unknown.is_empty() {
+                for id in unknown {
+                    report.add(error("authors", format!(
+                        "unknown author {:?}, expected one of the ids in the authors registry", id
+                    )));
+                }
+                continue;
+            }
+
+            let authors = article.authors.iter().map(|id| registry[id].clone()).collect();
+
+            // Hidden articles are still checked, and still count towards the numbering of their
+            // series and take up their language among translations
+            let visibility = article.visibility_at(now);
+            let index = visibility.map(|_| articles.len());
+
+            let taxonomy_error = |e: tera::Error| error("tags", format!(
+                "unable to generate slug for tag or category: {}", e
+            ));
+
+            let taxonomies = article.tags.iter()
+                .map(|tag| Taxonomy::new(tag))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|tags| Ok((tags, match &article.category {
+                    Some(category) => Some(Taxonomy::new(category)?),
+                    None => None,
+                })));
+
+            let (tags, category) = match taxonomies {
+                Ok(taxonomies) => taxonomies,
+                Err(e) => {
+                    report.add(taxonomy_error(e));
+                    continue;
+                }
+            };
+
+            match (&article.series, article.part) {
+                (Some(name), Some(part)) => match Taxonomy::new(name) {
+                    Ok(taxonomy) => memberships
+                        .entry(taxonomy.slug.clone())
+                        .or_insert_with(|| (taxonomy, Vec::new()))
+                        .1
+                        .push((part, index, source)),
+                    Err(e) => {
+                        report.add(taxonomy_error(e));
+                        continue;
+                    }
+                },
+                (Some(name), None) => {
+                    report.add(error("series", format!(
+                        "article is part of the series {:?}, but has no part number", name
+                    )));
+                    continue;
+                }
+                (None, Some(_)) => {
+                    report.add(error("part", "article has a part number, but is not part of a series".into()));
+                    continue;
+                }
+                (None, None) => (),
+            }
+
+            if let Some(key) = &article.translation {
+                translations.entry(key.clone()).or_default().push((language.clone(), index, source));
+            }
+
+            let visibility = match visibility {
+                Some(visibility) => visibility,
+                None => {
+                    if article.visibility != Visibility::Draft {
+                        next_scheduled = Some(match next_scheduled {
+                            Some(next) => next.min(article.published),
+                            None => article.published,
+                        });
+                    }
+                    continue;
+                }
+            };
+
+            articles.push(Article {
+                title: article.title.clone(),
+                authors,
+                published: article.published,
+                updated: article.updated.unwrap_or(article.published),
+                body: analysis.body,
+                excerpt,
+                words: analysis.words,
+                reading_time: analysis.reading_time,
+                toc: analysis.toc,
+                url: article_url(&language, &config.language, &slug),
+                slug,
+                aliases: article.aliases.clone(),
+                language,
+                translations: Vec::new(),
+                tags,
+                category,
+                series: None,
+                related: Vec::new(),
+                visibility,
+            });
+            article_sources.push(source);
+        }
+
+        // Unlisted parts are still included in their series, since
+        // leaving them out would leave gaps in the numbering
+        let series = link_series(&mut articles, memberships, report);
+        link_translations(&mut articles, translations, config, report);
+
+        for (index, similar) in related::similar(&articles, config.related_articles).into_iter().enumerate() {
+            articles[index].related = similar.into_iter()
+                .map(|other| ArticleLink::from(&articles[other]))
+                .collect();
+        }
+
+        let mut tags = BTreeMap::new();
+        let mut categories = BTreeMap::new();
+
+        for (index, article) in articles.iter().enumerate().filter(|(_, article)| article.is_listed()) {
+            for tag in &article.tags {
+                add_to_group(&mut tags, tag, index);
+            }
+
+            if let Some(category) = &article.category {
+                add_to_group(&mut categories, category, index);
+            }
+        }
+
+        // Unlike tags and categories, authors get a profile page even if
+        // they have no listed articles, since it can be linked to directly
+        let mut authors: BTreeMap<String, Profile> = registry.values()
+            .map(|author| (author.slug.clone(), Profile {
+                author: author.clone(),
+                articles: Vec::new(),
+            }))
+            .collect();
+
+        for (index, article) in articles.iter().enumerate().filter(|(_, article)| article.is_listed()) {
+            for author in &article.authors {
+                if let Some(profile) = authors.get_mut(&author.slug) {
+                    profile.articles.push(index);
+                }
+            }
+        }
+
+        // Slugs are matched regardless of casing when redirecting, so
+        // former slugs must be unique even when ignoring casing
+        let mut redirects = HashMap::new();
+        for (index, article) in articles.iter().enumerate() {
+            redirects.insert(article.slug.to_lowercase(), index);
+        }
+
+        for (index, article) in articles.iter().enumerate() {
+            let source = article_sources[index];
+
+            for alias in &article.aliases {
+                if !is_valid_slug(alias) {
+                    report.add(Problem::error(&source.path, source.line_of("aliases"), format!(
+                        "invalid former slug {:?}, expected only lowercase letters, digits and dashes", alias
+                    )));
+                    continue;
+                }
+
+                let existing = match slugs.get(alias.as_str()) {
+                    Some(existing) => Some(*existing),
+                    None => match redirects.get(&alias.to_lowercase()) {
+                        Some(&other) if other != index => Some(article_sources[other].path.as_path()),
+                        _ => None,
+                    },
                 };
 
-                Article {
-                    title: article.title,
-                    author: article.author,
-                    published: article.published,
-                    body,
-                    slug,
+                match existing {
+                    Some(existing) => report.add(Problem::error(&source.path, source.line_of("aliases"), format!(
+                        "former slug {:?} is already used by {}", alias, existing.display()
+                    ))),
+                    None => {
+                        redirects.insert(alias.to_lowercase(), index);
+                    }
                 }
+            }
+        }
+
+        let search = SearchIndex::new(&articles);
+
+        World { articles, tags, categories, series, authors, next_scheduled, redirects, search }
+    }
+
+    /// Articles which should appear on the frontpage and in other listings
+    pub fn listed(&self) -> Vec<&Article> {
+        self.articles.iter().filter(|article| article.is_listed()).collect()
+    }
+
+    /// Resolves the articles of a group
+    pub fn articles_in(&self, group: &Group) -> Vec<&Article> {
+        group.articles.iter().map(|&index| &self.articles[index]).collect()
+    }
+
+    pub fn find_tag(&self, slug: &str) -> Result<&Group, BlogError> {
+        self.tags.get(slug).ok_or_else(|| BlogError::MissingContent(
+            "no tag with that name found".into()
+        ))
+    }
+
+    pub fn find_category(&self, slug: &str) -> Result<&Group, BlogError> {
+        self.categories.get(slug).ok_or_else(|| BlogError::MissingContent(
+            "no category with that name found".into()
+        ))
+    }
+
+    pub fn find_series(&self, slug: &str) -> Result<&Group, BlogError> {
+        self.series.get(slug).ok_or_else(|| BlogError::MissingContent(
+            "no series with that name found".into()
+        ))
+    }
+
+    pub fn find_author(&self, slug: &str) -> Result<&Profile, BlogError> {
+        self.authors.get(slug).ok_or_else(|| BlogError::MissingContent(
+            "no author with that name found".into()
+        ))
+    }
+
+    pub fn articles_by(&self, profile: &Profile) -> Vec<&Article> {
+        profile.articles.iter().map(|&index| &self.articles[index]).collect()
+    }
+
+    pub fn tag_summaries(&self) -> Vec<GroupSummary<'_>> {
+        self.tags.values()
+            .map(|group| GroupSummary {
+                name: &group.taxonomy.name,
+                slug: &group.taxonomy.slug,
+                count: group.articles.len(),
             })
-            .collect();
+            .collect()
+    }
 
-        World { articles }
+    /// Current path of the article with the given former slug, or
+    /// with the given slug written with different casing
+    pub fn canonical_url(&self, slug: &str) -> Option<&str> {
+        self.redirects.get(&slug.to_lowercase())
+            .map(|&index| self.articles[index].url.as_str())
     }
 
-    pub fn find_by_slug(&'a self, slug: &str) -> Result<&'a Article<'a>, BlogError> {
+    pub fn find_by_slug(&self, slug: &str) -> Result<&Article, BlogError> {
         let article = self.articles.iter().find(|&article| article.slug == slug);
 
         match article {
@@ -83,3 +821,133 @@ impl<'a> World<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn config() -> Config {
+        Config {
+            languages: vec![("en", "English"), ("da", "Dansk"), ("de", "Deutsch")].into_iter()
+                .map(|(code, name)| (code.to_string(), name.to_string()))
+                .collect(),
+            ..Config::default()
+        }
+    }
+
+    fn article(slug: &str, language: &str) -> Article {
+        Article {
+            title: slug.to_uppercase(),
+            authors: Vec::new(),
+            published: 0,
+            updated: 0,
+            body: String::new(),
+            excerpt: String::new(),
+            words: 0,
+            reading_time: 1,
+            toc: Vec::new(),
+            slug: slug.into(),
+            url: article_url(language, "en", slug),
+            aliases: Vec::new(),
+            language: language.into(),
+            translations: Vec::new(),
+            tags: Vec::new(),
+            category: None,
+            series: None,
+            related: Vec::new(),
+            visibility: Visibility::Public,
+        }
+    }
+
+    fn source(slug: &str) -> ArticleSource {
+        let front_matter = format!("title: {}\nauthors: []\npublished: 0\ntranslation: hello\n", slug);
+
+        ArticleSource {
+            path: PathBuf::from(format!("{}.md", slug)),
+            metadata: serde_yaml::from_str(&front_matter).unwrap(),
+            front_matter,
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn articles_in_other_languages_get_a_prefix() {
+        assert_eq!(article_url("en", "en", "hello"), "/articles/hello");
+        assert_eq!(article_url("da", "en", "hej"), "/da/articles/hej");
+        assert_eq!(article_url("en", "da", "hello"), "/en/articles/hello");
+    }
+
+    #[test]
+    fn translations_link_to_each_other() {
+        let mut articles = vec![article("hello", "en"), article("hej", "da"), article("hallo", "de")];
+        let sources = [source("hello"), source("hej"), source("hallo")];
+
+        let mut groups = BTreeMap::new();
+        groups.insert("hello".to_string(), vec![
+            ("en".to_string(), Some(0), &sources[0]),
+            ("da".to_string(), Some(1), &sources[1]),
+            ("de".to_string(), Some(2), &sources[2]),
+        ]);
+
+        let mut report = Report::default();
+        link_translations(&mut articles, groups, &config(), &mut report);
+        assert!(report.is_empty());
+
+        let languages = |article: &Article| article.translations.iter()
+            .map(|translation| (translation.language.clone(), translation.name.clone(), translation.url.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(languages(&articles[0]), vec![
+            ("da".to_string(), "Dansk".to_string(), "/da/articles/hej".to_string()),
+            ("de".to_string(), "Deutsch".to_string(), "/de/articles/hallo".to_string()),
+        ]);
+        assert_eq!(articles[1].translations.iter().map(|t| t.title.as_str()).collect::<Vec<_>>(), vec!["HALLO", "HELLO"]);
+        assert_eq!(articles[2].translations.len(), 2);
+    }
+
+    #[test]
+    fn languages_are_only_taken_once_per_translation() {
+        let mut articles = vec![article("hello", "en"), article("hej", "da"), article("goddag", "da")];
+        let sources = [source("hello"), source("hej"), source("goddag"), source("draft")];
+
+        let mut groups = BTreeMap::new();
+        groups.insert("hello".to_string(), vec![
+            ("en".to_string(), Some(0), &sources[0]),
+            ("da".to_string(), Some(1), &sources[1]),
+            ("da".to_string(), Some(2), &sources[2]),
+            ("en".to_string(), None, &sources[3]),
+        ]);
+
+        let mut report = Report::default();
+        link_translations(&mut articles, groups, &config(), &mut report);
+
+        let problems: Vec<String> = report.problems.iter().map(ToString::to_string).collect();
+        assert_eq!(problems, vec![
+            "error: goddag.md:5: translation \"hello\" already has an article in \"da\": hej.md",
+            "error: draft.md:5: translation \"hello\" already has an article in \"en\": hello.md",
+        ]);
+
+        assert_eq!(articles[0].translations.len(), 1);
+        assert_eq!(articles[1].translations[0].url, "/articles/hello");
+        assert!(articles[2].translations.is_empty());
+    }
+
+    #[test]
+    fn hidden_translations_take_up_their_language() {
+        let mut articles = vec![article("hej", "da")];
+        let sources = [source("draft"), source("hej")];
+
+        let mut groups = BTreeMap::new();
+        groups.insert("hello".to_string(), vec![
+            ("da".to_string(), None, &sources[0]),
+            ("da".to_string(), Some(0), &sources[1]),
+        ]);
+
+        let mut report = Report::default();
+        link_translations(&mut articles, groups, &config(), &mut report);
+
+        assert_eq!(report.errors(), 1);
+        assert!(articles[0].translations.is_empty());
+    }
+}
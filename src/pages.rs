@@ -0,0 +1,143 @@
+use tera::Context;
+use crate::archive::{by_month, neighbours, years};
+use crate::error::BlogError;
+use crate::pagination::paginate;
+use crate::site::Site;
+
+// Rendering of every HTML page on the blog. The context passed in holds whatever
+// the page is embedded in, such as server statistics, which differs between
+// pages served live and pages exported to disk.
+
+/// Renders a page in the given language, along with the highlighting
+/// settings deciding which style sheets the code on the page needs
+fn render_in(site: &Site, template: &str, mut ctx: Context, language: &str) -> Result<String, BlogError> {
+    ctx.insert("language", language);
+    ctx.insert("highlight", &site.config.highlight);
+    Ok(site.tera.render(template, &ctx)?)
+}
+
+/// Renders a page which isn't written in any language in particular,
+/// and as such is considered to be in the default language of the site
+fn render(site: &Site, template: &str, ctx: Context) -> Result<String, BlogError> {
+    render_in(site, template, ctx, &site.config.language)
+}
+
+pub fn frontpage_url(page: usize) -> String {
+    match page {
+        1 => "/".into(),
+        page => format!("/page/{}", page),
+    }
+}
+
+pub fn frontpage(site: &Site, mut ctx: Context, page: usize) -> Result<String, BlogError> {
+    let articles = site.world.listed();
+    let (articles, pagination) = paginate(&articles, page, site.config.page_size, frontpage_url)
+        .ok_or_else(|| BlogError::MissingContent("no page with that number found".into()))?;
+
+    ctx.insert("articles", articles);
+    ctx.insert("pagination", &pagination);
+    render(site, "frontpage.tera", ctx)
+}
+
+pub fn archive(site: &Site, mut ctx: Context) -> Result<String, BlogError> {
+    let months = by_month(&site.world.listed());
+
+    ctx.insert("heading", "Archive");
+    ctx.insert("months", &months);
+    render(site, "archive.tera", ctx)
+}
+
+pub fn archive_year(site: &Site, mut ctx: Context, year: i32) -> Result<String, BlogError> {
+    let months = by_month(&site.world.listed());
+
+    let pagination = neighbours(
+        &years(&months),
+        &year,
+        |year| format!("/archive/{}", year),
+        |year| year.to_string(),
+    ).ok_or_else(|| BlogError::MissingContent("no articles published that year".into()))?;
+
+    let months: Vec<_> = months.into_iter().filter(|month| month.year == year).collect();
+
+    ctx.insert("heading", &year.to_string());
+    ctx.insert("months", &months);
+    ctx.insert("pagination", &pagination);
+    render(site, "archive.tera", ctx)
+}
+
+pub fn archive_month(site: &Site, mut ctx: Context, year: i32, month: u32) -> Result<String, BlogError> {
+    let months = by_month(&site.world.listed());
+    let keys: Vec<_> = months.iter().map(|month| (month.year, month.month)).collect();
+
+    let month_name = |&(year, month): &(i32, u32)| {
+        chrono::NaiveDate::from_ymd(year, month, 1).format("%B %Y").to_string()
+    };
+
+    let pagination = neighbours(
+        &keys,
+        &(year, month),
+        |(year, month)| format!("/archive/{}/{}", year, month),
+        month_name,
+    ).ok_or_else(|| BlogError::MissingContent("no articles published that month".into()))?;
+
+    let months: Vec<_> = months.into_iter()
+        .filter(|archived| archived.year == year && archived.month == month)
+        .collect();
+
+    ctx.insert("heading", &month_name(&(year, month)));
+    ctx.insert("months", &months);
+    ctx.insert("pagination", &pagination);
+    render(site, "archive.tera", ctx)
+}
+
+pub fn article(site: &Site, mut ctx: Context, slug: &str) -> Result<String, BlogError> {
+    let article = site.world.find_by_slug(slug)?;
+
+    ctx.insert("article", &article);
+    // Alternate links to translations must be absolute
+    ctx.insert("base_url", &site.config.base_url);
+    render_in(site, "single-article.tera", ctx, &article.language)
+}
+
+pub fn search(site: &Site, mut ctx: Context, query: &str) -> Result<String, BlogError> {
+    ctx.insert("query", query);
+    ctx.insert("results", &site.world.search.search(&site.world.articles, query));
+    render(site, "search.tera", ctx)
+}
+
+pub fn tags(site: &Site, mut ctx: Context) -> Result<String, BlogError> {
+    ctx.insert("tags", &site.world.tag_summaries());
+    render(site, "tags.tera", ctx)
+}
+
+pub fn tag(site: &Site, mut ctx: Context, slug: &str) -> Result<String, BlogError> {
+    let tag = site.world.find_tag(slug)?;
+
+    ctx.insert("heading", &format!("Articles tagged #{}", tag.taxonomy.name));
+    ctx.insert("articles", &site.world.articles_in(tag));
+    render(site, "listing.tera", ctx)
+}
+
+pub fn category(site: &Site, mut ctx: Context, slug: &str) -> Result<String, BlogError> {
+    let category = site.world.find_category(slug)?;
+
+    ctx.insert("heading", &format!("Articles in {}", category.taxonomy.name));
+    ctx.insert("articles", &site.world.articles_in(category));
+    render(site, "listing.tera", ctx)
+}
+
+pub fn series(site: &Site, mut ctx: Context, slug: &str) -> Result<String, BlogError> {
+    let series = site.world.find_series(slug)?;
+
+    ctx.insert("series", &series.taxonomy);
+    ctx.insert("articles", &site.world.articles_in(series));
+    render(site, "series.tera", ctx)
+}
+
+pub fn author(site: &Site, mut ctx: Context, slug: &str) -> Result<String, BlogError> {
+    let profile = site.world.find_author(slug)?;
+
+    ctx.insert("author", &profile.author);
+    ctx.insert("articles", &site.world.articles_by(profile));
+    render(site, "author.tera", ctx)
+}
@@ -0,0 +1,93 @@
+#[derive(Serialize)]
+pub struct PageLink {
+    pub url: String,
+    pub label: String,
+}
+
+/// Position within a sequence of pages, with links to the neighbouring ones.
+/// `previous` always points towards newer content, and `next` towards older.
+#[derive(Serialize)]
+pub struct Pagination {
+    pub current: usize,
+    pub total: usize,
+    pub previous: Option<PageLink>,
+    pub next: Option<PageLink>,
+}
+
+/// Splits the items into pages of the given size and returns the requested page,
+/// counting from 1, or None if there is no such page. The first page always exists,
+/// even if there are no items at all.
+pub fn paginate<T, F>(items: &[T], page: usize, size: usize, url: F)
+    -> Option<(&[T], Pagination)>
+where
+    F: Fn(usize) -> String
+{
+    let total = std::cmp::max(1, items.len().div_ceil(size));
+    if page == 0 || page > total {
+        return None;
+    }
+
+    let start = (page - 1) * size;
+    let end = std::cmp::min(start + size, items.len());
+
+    let pagination = Pagination {
+        current: page,
+        total,
+        previous: if page > 1 {
+            Some(PageLink { url: url(page - 1), label: "Newer posts".into() })
+        } else {
+            None
+        },
+        next: if page < total {
+            Some(PageLink { url: url(page + 1), label: "Older posts".into() })
+        } else {
+            None
+        },
+    };
+
+    Some((&items[start..end], pagination))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(page: usize) -> String {
+        format!("/page/{}", page)
+    }
+
+    #[test]
+    fn splits_items_into_pages() {
+        let items: Vec<usize> = (0..25).collect();
+
+        let (first, pagination) = paginate(&items, 1, 10, url).unwrap();
+        assert_eq!(first, &items[0..10]);
+        assert_eq!(pagination.total, 3);
+        assert!(pagination.previous.is_none());
+        assert_eq!(pagination.next.unwrap().url, "/page/2");
+
+        let (last, pagination) = paginate(&items, 3, 10, url).unwrap();
+        assert_eq!(last, &items[20..25]);
+        assert_eq!(pagination.previous.unwrap().url, "/page/2");
+        assert!(pagination.next.is_none());
+    }
+
+    #[test]
+    fn first_page_always_exists() {
+        let items: Vec<usize> = Vec::new();
+
+        let (page, pagination) = paginate(&items, 1, 10, url).unwrap();
+        assert!(page.is_empty());
+        assert_eq!(pagination.total, 1);
+        assert!(pagination.previous.is_none() && pagination.next.is_none());
+    }
+
+    #[test]
+    fn rejects_pages_out_of_range() {
+        let items: Vec<usize> = (0..20).collect();
+
+        assert!(paginate(&items, 0, 10, url).is_none());
+        assert!(paginate(&items, 2, 10, url).is_some());
+        assert!(paginate(&items, 3, 10, url).is_none());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use crate::analysis::{plain_text, tokenize};
+use crate::model::Article;
+
+/// Words too common to say anything about what an article is about
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "because",
+    "been", "but", "by", "can", "do", "for", "from", "has", "have", "how", "i", "if", "in", "into",
+    "is", "it", "its", "just", "like", "more", "my", "no", "not", "of", "on", "one", "or", "our",
+    "so", "some", "than", "that", "the", "their", "then", "there", "these", "they", "this", "to",
+    "up", "us", "was", "we", "what", "when", "which", "will", "with", "would", "you", "your",
+];
+
+/// How many times more a word in the title counts than one in the body
+const TITLE_WEIGHT: f64 = 3.0;
+
+/// Term frequencies of an article, with words in the title weighted higher
+fn term_frequencies(article: &Article) -> BTreeMap<String, f64> {
+    let mut terms = BTreeMap::new();
+    let text = plain_text(&article.body);
+
+    let title = tokenize(&article.title).map(|word| (word, TITLE_WEIGHT));
+    let body = tokenize(&text).map(|word| (word, 1.0));
+
+    for (word, weight) in title.chain(body) {
+        if word.len() > 1 && !STOP_WORDS.contains(&word.as_str()) {
+            *terms.entry(word).or_insert(0.0) += weight;
+        }
+    }
+
+    terms
+}
+
+/// Finds the `count` articles most similar to each article, by the cosine similarity of their
+/// TF-IDF vectors. Only listed articles are suggested, and articles sharing no significant words
+/// aren't suggested at all. Ties are broken by position, so the result is entirely deterministic.
+pub fn similar(articles: &[Article], count: usize) -> Vec<Vec<usize>> {
+    let frequencies: Vec<BTreeMap<String, f64>> = articles.iter().map(term_frequencies).collect();
+
+    let mut documents: BTreeMap<&str, usize> = BTreeMap::new();
+    for terms in &frequencies {
+        for word in terms.keys() {
+            *documents.entry(word).or_insert(0) += 1;
+        }
+    }
+
+    // Smoothed, so that words found in every article still carry a little weight
+    let total = articles.len() as f64;
+    let vectors: Vec<BTreeMap<&str, f64>> = frequencies.iter()
+        .map(|terms| {
+            let mut vector: BTreeMap<&str, f64> = terms.iter()
+                .map(|(word, frequency)| {
+                    let idf = ((1.0 + total) / (1.0 + documents[word.as_str()] as f64)).ln() + 1.0;
+                    (word.as_str(), frequency * idf)
+                })
+                .collect();
+
+            let length = vector.values().map(|weight| weight * weight).sum::<f64>().sqrt();
+            if length > 0.0 {
+                for weight in vector.values_mut() {
+                    *weight /= length;
+                }
+            }
+
+            vector
+        })
+        .collect();
+
+    vectors.iter().enumerate()
+        .map(|(index, vector)| {
+            let mut scores: Vec<(f64, usize)> = vectors.iter().enumerate()
+                .filter(|&(other, _)| other != index && articles[other].is_listed())
+                .map(|(other, candidate)| {
+                    let score = vector.iter()
+                        .filter_map(|(word, weight)| candidate.get(word).map(|other| weight * other))
+                        .sum::<f64>();
+                    (score, other)
+                })
+                .filter(|&(score, _)| score > 0.0)
+                .collect();
+
+            scores.sort_by(|(a, first), (b, second)| b.partial_cmp(a).unwrap().then(first.cmp(second)));
+            scores.into_iter().take(count).map(|(_, other)| other).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Visibility;
+
+    fn article(title: &str, body: &str) -> Article {
+        Article {
+            title: title.into(),
+            authors: Vec::new(),
+            published: 0,
+            updated: 0,
+            body: format!("<p>{}</p>", body),
+            excerpt: String::new(),
+            words: 0,
+            reading_time: 1,
+            toc: Vec::new(),
+            slug: String::new(),
+            url: String::new(),
+            aliases: Vec::new(),
+            language: "en".into(),
+            translations: Vec::new(),
+            tags: Vec::new(),
+            category: None,
+            series: None,
+            related: Vec::new(),
+            visibility: Visibility::Public,
+        }
+    }
+
+    #[test]
+    fn most_similar_articles_come_first() {
+        let articles = vec![
+            article("Borrowing in Rust", "lifetimes borrow checker compiler"),
+            article("Gardening", "soil watering seeds"),
+            article("Rust compiler", "borrow checker compiler errors"),
+            article("Rust", "pasta garlic tomatoes recipes"),
+        ];
+
+        let similar = similar(&articles, 3);
+        assert_eq!(similar[0], vec![2, 3]);
+        assert!(similar[1].is_empty());
+    }
+
+    #[test]
+    fn ties_are_broken_by_position() {
+        let articles = vec![
+            article("Same", "identical words"),
+            article("Same", "identical words"),
+            article("Same", "identical words"),
+            article("Same", "identical words"),
+        ];
+
+        let similar = similar(&articles, 2);
+        assert_eq!(similar[0], vec![1, 2]);
+        assert_eq!(similar[2], vec![0, 1]);
+        assert_eq!(similar[3], vec![0, 1]);
+    }
+
+    #[test]
+    fn only_listed_articles_are_suggested() {
+        let mut articles = vec![
+            article("Rust", "compiler"),
+            article("Rust", "compiler"),
+            article("Rust", "compiler"),
+        ];
+        articles[1].visibility = Visibility::Unlisted;
+
+        let similar = similar(&articles, 3);
+        assert_eq!(similar[0], vec![2]);
+        assert_eq!(similar[1], vec![0, 2]);
+    }
+
+    #[test]
+    fn results_are_the_same_every_time() {
+        let articles: Vec<Article> = (0..10)
+            .map(|index| article(&format!("Part {}", index % 3), "shared words across articles"))
+            .collect();
+
+        assert_eq!(similar(&articles, 3), similar(&articles, 3));
+    }
+}
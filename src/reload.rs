@@ -0,0 +1,80 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+use actix_web::web::Data;
+use notify::{watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use crate::site::{SiteState, AUTHORS_FILE, CONFIG_FILE, CONTENT_DIR, STATIC_DIR, TEMPLATE_DIR};
+
+/// Editors tend to touch files several times when saving, so changes
+/// are collected for a short while before triggering a rebuild
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Directories whose entire contents are watched
+const DIRS: &[&str] = &[TEMPLATE_DIR, CONTENT_DIR, STATIC_DIR];
+
+/// Files watched through the directory they're in. Editors often save by writing a new
+/// file and renaming it over the old one, which a watch on the file itself wouldn't survive.
+const FILES: &[&str] = &[AUTHORS_FILE, CONFIG_FILE];
+
+/// Paths of the events the watcher reports, none for a rescan
+fn paths(event: &DebouncedEvent) -> Vec<&PathBuf> {
+    match event {
+        DebouncedEvent::Create(path) | DebouncedEvent::Write(path) | DebouncedEvent::Chmod(path)
+        | DebouncedEvent::Remove(path) => vec![path],
+        DebouncedEvent::Rename(from, to) => vec![from, to],
+        _ => Vec::new(),
+    }
+}
+
+/// Watches the templates, content and static directories as well as the authors registry
+/// and configuration, and rebuilds the site whenever anything changes. The watcher stops
+/// as soon as the returned value is dropped.
+pub fn watch(state: Data<SiteState>) -> notify::Result<RecommendedWatcher> {
+    let (tx, rx) = channel();
+    let mut watcher = watcher(tx, DEBOUNCE)?;
+
+    // Events carry absolute paths, made so against the current directory like the watches
+    let current = env::current_dir().map_err(notify::Error::Io)?;
+    let dirs: Vec<PathBuf> = DIRS.iter().map(|dir| current.join(dir)).collect();
+    let files: Vec<PathBuf> = FILES.iter().map(|file| current.join(file)).collect();
+
+    for dir in &dirs {
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+    }
+    let parents: BTreeSet<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+    for parent in parents {
+        watcher.watch(parent, RecursiveMode::NonRecursive)?;
+    }
+
+    thread::spawn(move || {
+        for event in rx {
+            match event {
+                // These are sent immediately and will be followed by a debounced
+                // event once the file has settled, so we wait for that one instead
+                DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) => continue,
+                DebouncedEvent::Error(e, path) => {
+                    eprintln!("File watcher error for {:?}: {}", path, e);
+                    continue;
+                }
+                _ => (),
+            }
+
+            // Besides the files, the directories holding them have other contents to ignore
+            let paths = paths(&event);
+            let relevant = |path: &&PathBuf| files.contains(path) || dirs.iter().any(|dir| path.starts_with(dir));
+            if !paths.is_empty() && !paths.iter().any(relevant) {
+                continue;
+            }
+
+            match state.reload() {
+                Ok(()) => println!("Reloaded templates and articles"),
+                Err(e) => eprintln!("Failed to reload, still serving the previous version: {}", e),
+            }
+        }
+    });
+
+    Ok(watcher)
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+use actix::prelude::*;
+use actix_web::web::{self, Data};
+use crate::model::current_time;
+use crate::site::{Reloaded, SiteState};
+
+/// Upper bound on how long to wait at a time, so articles scheduled far
+/// into the future don't run into the limits of the underlying timer
+const MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// How long to wait before trying again if the site failed to reload
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Reloads the site the moment the next scheduled article is due, so
+/// it appears without having to restart the server or touch any files
+pub struct Publisher {
+    state: Data<SiteState>,
+    timer: Option<SpawnHandle>,
+}
+
+impl Publisher {
+    pub fn new(state: Data<SiteState>) -> Self {
+        Publisher { state, timer: None }
+    }
+
+    fn schedule(&mut self, ctx: &mut Context<Self>) {
+        if let Some(timer) = self.timer.take() {
+            ctx.cancel_future(timer);
+        }
+
+        let next = match self.state.current().world.next_scheduled {
+            Some(next) => next,
+            None => return,
+        };
+
+        let now = current_time();
+        if next <= now {
+            self.publish(ctx);
+            return;
+        }
+
+        let delay = std::cmp::min(Duration::from_secs(next - now), MAX_DELAY);
+        self.timer = Some(ctx.run_later(delay, |act, ctx| {
+            act.timer = None;
+            act.schedule(ctx);
+        }));
+    }
+
+    /// Reloading notifies every subscriber, including this one, which is what
+    /// schedules the timer for the next article. Loading the site takes a while,
+    /// so it happens on the blocking thread pool rather than holding up the arbiter.
+    fn publish(&mut self, ctx: &mut Context<Self>) {
+        let state = self.state.clone();
+
+        ctx.spawn(web::block(move || state.reload()).into_actor(self).then(|result, act, ctx| {
+            match result {
+                Ok(()) => println!("Published scheduled articles"),
+                Err(e) => {
+                    eprintln!("Failed to publish scheduled articles, trying again shortly: {}", e);
+                    act.timer = Some(ctx.run_later(RETRY_DELAY, |act, ctx| {
+                        act.timer = None;
+                        act.schedule(ctx);
+                    }));
+                }
+            }
+
+            fut::ok(())
+        }));
+    }
+}
+
+impl Actor for Publisher {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.schedule(ctx);
+    }
+}
+
+impl Handler<Reloaded> for Publisher {
+    type Result = ();
+
+    fn handle(&mut self, _: Reloaded, ctx: &mut Context<Self>) {
+        self.schedule(ctx);
+    }
+}
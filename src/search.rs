@@ -0,0 +1,339 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::analysis::{escape, plain_text};
+use crate::model::Article;
+
+/// Most results returned for a single query
+const MAX_RESULTS: usize = 50;
+
+/// Words of context shown around the first match in a snippet
+const SNIPPET_BEFORE: usize = 10;
+const SNIPPET_AFTER: usize = 20;
+
+/// The parts of an article which are indexed, each weighted differently when ranking
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Field {
+    Title,
+    /// Tags, category, series and author names
+    Metadata,
+    Body,
+}
+
+impl Field {
+    fn weight(self) -> f64 {
+        match self {
+            Field::Title => 5.0,
+            Field::Metadata => 3.0,
+            Field::Body => 1.0,
+        }
+    }
+}
+
+/// Occurrences of a word within a single field of an article
+struct Posting {
+    article: usize,
+    field: Field,
+    positions: Vec<usize>,
+}
+
+/// Plain text of an article body, along with where each of its words are
+struct Document {
+    text: String,
+    /// Byte range of every word in `text`, indexed by position
+    words: Vec<(usize, usize)>,
+}
+
+/// A word, a prefix or a phrase, all of which must match for an article to be found
+#[derive(Debug, PartialEq)]
+enum Clause {
+    Word(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+/// An article matching a query, along with a snippet of its body with the matches highlighted
+#[derive(Serialize)]
+pub struct SearchResult<'a> {
+    pub title: &'a str,
+    pub slug: &'a str,
+    pub url: &'a str,
+    pub published: u64,
+    pub score: f64,
+    /// HTML-escaped text with every match wrapped in `<mark>`
+    pub snippet: String,
+}
+
+/// Inverted index over the titles, metadata and bodies of every listed article
+pub struct SearchIndex {
+    terms: BTreeMap<String, Vec<Posting>>,
+    documents: BTreeMap<usize, Document>,
+}
+
+/// Lowercased words of the text along with their byte ranges, split the same way as `analysis::tokenize`
+fn words(text: &str) -> Vec<(String, usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+
+    for (offset, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(offset),
+            (Some(from), false) => {
+                words.push((text[from..offset].to_lowercase(), from, offset));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    words
+}
+
+/// Splits a query into clauses. Quoted parts are phrases, and words ending in * match any word
+/// starting with them. Phrases of a single word, such as "rust", are treated as plain words.
+fn parse(query: &str) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+
+    for (index, part) in query.split('"').enumerate() {
+        // Every other part is between quotes, assuming they are balanced
+        if index % 2 == 1 {
+            let phrase: Vec<String> = words(part).into_iter().map(|(word, _, _)| word).collect();
+            match phrase.len() {
+                0 => (),
+                1 => clauses.push(Clause::Word(phrase.into_iter().next().unwrap())),
+                _ => clauses.push(Clause::Phrase(phrase)),
+            }
+            continue;
+        }
+
+        for term in part.split_whitespace() {
+            let prefix = term.ends_with('*');
+            for (word, _, _) in words(term) {
+                clauses.push(match prefix {
+                    true => Clause::Prefix(word),
+                    false => Clause::Word(word),
+                });
+            }
+        }
+    }
+
+    clauses
+}
+
+impl SearchIndex {
+    pub fn new(articles: &[Article]) -> SearchIndex {
+        let mut terms: BTreeMap<String, Vec<Posting>> = BTreeMap::new();
+        let mut documents = BTreeMap::new();
+
+        for (index, article) in articles.iter().enumerate().filter(|(_, article)| article.is_listed()) {
+            let mut metadata: Vec<&str> = article.tags.iter().map(|tag| tag.name.as_str()).collect();
+            metadata.extend(article.category.iter().map(|category| category.name.as_str()));
+            metadata.extend(article.series.iter().map(|series| series.name.as_str()));
+            metadata.extend(article.authors.iter().map(|author| author.name.as_str()));
+
+            let body = plain_text(&article.body);
+            let body_words = words(&body);
+
+            let fields = vec![
+                (Field::Title, words(&article.title)),
+                (Field::Metadata, words(&metadata.join(" "))),
+                (Field::Body, body_words.clone()),
+            ];
+
+            for (field, words) in fields {
+                let mut positions: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+                for (position, (word, _, _)) in words.into_iter().enumerate() {
+                    positions.entry(word).or_default().push(position);
+                }
+
+                for (word, positions) in positions {
+                    terms.entry(word).or_default().push(Posting { article: index, field, positions });
+                }
+            }
+
+            documents.insert(index, Document {
+                words: body_words.into_iter().map(|(_, from, to)| (from, to)).collect(),
+                text: body,
+            });
+        }
+
+        SearchIndex { terms, documents }
+    }
+
+    /// Postings of every word matching the clause, or starting positions for phrases
+    fn matches(&self, clause: &Clause) -> Vec<(usize, Field, Vec<usize>)> {
+        match clause {
+            Clause::Word(word) => self.terms.get(word)
+                .map(|postings| postings.iter()
+                    .map(|posting| (posting.article, posting.field, posting.positions.clone()))
+                    .collect())
+                .unwrap_or_default(),
+            Clause::Prefix(prefix) => self.terms.range(prefix.clone()..)
+                .take_while(|(word, _)| word.starts_with(prefix.as_str()))
+                .flat_map(|(_, postings)| postings.iter())
+                .map(|posting| (posting.article, posting.field, posting.positions.clone()))
+                .collect(),
+            Clause::Phrase(phrase) => {
+                let first = self.matches(&Clause::Word(phrase[0].clone()));
+                let rest: Vec<BTreeMap<(usize, Field), BTreeSet<usize>>> = phrase[1..].iter()
+                    .map(|word| self.matches(&Clause::Word(word.clone())).into_iter()
+                        .map(|(article, field, positions)| ((article, field), positions.into_iter().collect()))
+                        .collect())
+                    .collect();
+
+                first.into_iter()
+                    .filter_map(|(article, field, positions)| {
+                        let starts: Vec<usize> = positions.into_iter()
+                            .filter(|&start| rest.iter().enumerate().all(|(offset, word)| {
+                                word.get(&(article, field))
+                                    .map(|positions| positions.contains(&(start + offset + 1)))
+                                    .unwrap_or(false)
+                            }))
+                            .collect();
+
+                        match starts.is_empty() {
+                            true => None,
+                            false => Some((article, field, starts)),
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Finds the articles matching every clause of the query, ranked by how often and where
+    /// they match, with rarer words counting for more. Ties go to the newest article.
+    pub fn search<'a>(&self, articles: &'a [Article], query: &str) -> Vec<SearchResult<'a>> {
+        let clauses = parse(query);
+        if clauses.is_empty() {
+            return Vec::new();
+        }
+
+        let total = self.documents.len() as f64;
+        let mut scores: BTreeMap<usize, f64> = BTreeMap::new();
+        // Positions within the body to highlight in the snippet
+        let mut highlights: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+
+        for (number, clause) in clauses.iter().enumerate() {
+            let matches = self.matches(clause);
+            let length = match clause {
+                Clause::Phrase(phrase) => phrase.len(),
+                _ => 1,
+            };
+
+            let found: BTreeSet<usize> = matches.iter().map(|&(article, _, _)| article).collect();
+            let idf = (1.0 + total / found.len().max(1) as f64).ln();
+
+            let mut clause_scores: BTreeMap<usize, f64> = BTreeMap::new();
+            for (article, field, positions) in matches {
+                *clause_scores.entry(article).or_insert(0.0) += field.weight() * positions.len() as f64 * idf;
+
+                if field == Field::Body {
+                    highlights.entry(article).or_default()
+                        .extend(positions.iter().flat_map(|&start| start..start + length));
+                }
+            }
+
+            // Every clause must match, so anything missing from this one is out
+            match number {
+                0 => scores = clause_scores,
+                _ => scores = scores.into_iter()
+                    .filter_map(|(article, score)| clause_scores.get(&article).map(|extra| (article, score + extra)))
+                    .collect(),
+            }
+        }
+
+        let mut ranked: Vec<(usize, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|(first, a), (second, b)| b.partial_cmp(a).unwrap().then(first.cmp(second)));
+
+        ranked.into_iter()
+            .take(MAX_RESULTS)
+            .map(|(index, score)| {
+                let article = &articles[index];
+                let empty = BTreeSet::new();
+
+                SearchResult {
+                    title: &article.title,
+                    slug: &article.slug,
+                    url: &article.url,
+                    published: article.published,
+                    score,
+                    snippet: self.snippet(index, highlights.get(&index).unwrap_or(&empty)),
+                }
+            })
+            .collect()
+    }
+
+    /// Text surrounding the first highlighted word of the body, or
+    /// the beginning of the body if only the title or metadata matched
+    fn snippet(&self, article: usize, highlights: &BTreeSet<usize>) -> String {
+        let document = &self.documents[&article];
+        if document.words.is_empty() {
+            return String::new();
+        }
+
+        let first = highlights.iter().next().cloned().unwrap_or(0);
+        let from = first.saturating_sub(SNIPPET_BEFORE);
+        let to = std::cmp::min(first + SNIPPET_AFTER, document.words.len() - 1);
+
+        let mut snippet = String::new();
+        if from > 0 {
+            snippet.push('…');
+        }
+
+        let mut offset = document.words[from].0;
+        for position in from..=to {
+            let (start, end) = document.words[position];
+            snippet.push_str(&escape(&document.text[offset..start]));
+
+            match highlights.contains(&position) {
+                true => snippet.push_str(&format!("<mark>{}</mark>", escape(&document.text[start..end]))),
+                false => snippet.push_str(&escape(&document.text[start..end])),
+            }
+            offset = end;
+        }
+
+        if to + 1 < document.words.len() {
+            snippet.push('…');
+        }
+
+        // Collapse the whitespace left behind by the markup of the body
+        snippet.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(word: &str) -> Clause {
+        Clause::Word(word.into())
+    }
+
+    #[test]
+    fn splits_queries_into_lowercase_words() {
+        assert_eq!(parse("Rust  Tera"), vec![word("rust"), word("tera")]);
+        assert_eq!(parse("actix-web"), vec![word("actix"), word("web")]);
+        assert!(parse("  ").is_empty());
+    }
+
+    #[test]
+    fn words_ending_in_a_star_are_prefixes() {
+        assert_eq!(parse("high*"), vec![Clause::Prefix("high".into())]);
+        assert_eq!(parse("syntax high*"), vec![word("syntax"), Clause::Prefix("high".into())]);
+        assert!(parse("*").is_empty());
+    }
+
+    #[test]
+    fn quoted_words_are_phrases() {
+        assert_eq!(
+            parse("\"Syntax Highlighting\" rust"),
+            vec![Clause::Phrase(vec!["syntax".into(), "highlighting".into()]), word("rust")]
+        );
+        assert_eq!(parse("\"rust\""), vec![word("rust")]);
+        assert!(parse("\"\"").is_empty());
+    }
+
+    #[test]
+    fn unbalanced_quotes_extend_to_the_end() {
+        assert_eq!(parse("\"static site"), vec![Clause::Phrase(vec!["static".into(), "site".into()])]);
+    }
+}
@@ -0,0 +1,236 @@
+use std::sync::{Arc, Mutex, RwLock};
+use actix::prelude::*;
+use tera::Tera;
+use crate::config::Config;
+use crate::content;
+use crate::error::BlogError;
+use crate::highlighter;
+use crate::model::World;
+use crate::validation::Report;
+
+pub const TEMPLATE_DIR: &str = "resources/templates";
+pub const CONTENT_DIR: &str = "resources/articles";
+pub const STATIC_DIR: &str = "resources/static";
+pub const AUTHORS_FILE: &str = "resources/authors.toml";
+pub const CONFIG_FILE: &str = "resources/config.toml";
+
+/// Configuration, templates and the articles rendered from them,
+/// which together make up everything needed to serve the blog.
+pub struct Site {
+    pub config: Config,
+    pub tera: Tera,
+    pub world: World,
+}
+
+impl Site {
+    /// Loads the site, adding every problem found along the way to the report. A site
+    /// is returned whenever loading got far enough to produce one, even if it has errors.
+    pub fn build(report: &mut Report) -> Option<Site> {
+        // The defaults are good enough for finding problems with everything else
+        let config = Config::load(CONFIG_FILE).unwrap_or_else(|e| {
+            report.add(e);
+            Config::default()
+        });
+
+        let mut tera = match Tera::new(&format!("{}/**/*", TEMPLATE_DIR)) {
+            Ok(tera) => tera,
+            Err(e) => {
+                report.add(BlogError::from(e));
+                return None;
+            }
+        };
+
+        let highlighting = config.highlight.clone();
+        tera.register_function("highlight", Box::new(move |args| highlighter::highlight(&highlighting, args)));
+        tera.register_filter("codeblock", highlighter::codeblock);
+
+        let authors = match content::load_authors(AUTHORS_FILE) {
+            Ok(authors) => authors,
+            Err(e) => {
+                report.add(e);
+                return None;
+            }
+        };
+
+        let world = World::new(&mut tera, CONTENT_DIR, &authors, &config, report);
+
+        Some(Site { config, tera, world })
+    }
+
+    /// Where requests for the given path should be permanently redirected to, if anywhere.
+    /// Paths from the redirect table take precedence over the former slugs of articles,
+    /// and both are matched regardless of any trailing slash. Articles requested under
+    /// the prefix of another language are redirected to the one they are written in.
+    pub fn redirect(&self, path: &str) -> Option<String> {
+        let trimmed = match path.trim_end_matches('/') {
+            "" => "/",
+            trimmed => trimmed,
+        };
+
+        let target = self.config.redirects.get(path)
+            .or_else(|| self.config.redirects.get(trimmed))
+            .cloned()
+            .or_else(|| {
+                let (prefix, slug) = trimmed.split_once("/articles/")?;
+                let known_prefix = match prefix.strip_prefix('/') {
+                    Some(language) => self.config.languages.contains_key(language),
+                    None => prefix.is_empty(),
+                };
+
+                if !known_prefix || slug.contains('/') {
+                    return None;
+                }
+
+                self.world.canonical_url(slug).map(String::from)
+            })?;
+
+        match target == path {
+            true => None,
+            false => Some(target),
+        }
+    }
+
+    /// Loads the site, failing with the full report if any errors were found.
+    /// Warnings alone don't prevent the site from loading, but are printed.
+    pub fn load() -> Result<Site, BlogError> {
+        let mut report = Report::default();
+
+        match Site::build(&mut report) {
+            Some(site) if !report.has_errors() => {
+                if !report.is_empty() {
+                    eprint!("{}", report);
+                }
+                Ok(site)
+            }
+            _ => Err(BlogError::ValidationError(report)),
+        }
+    }
+}
+
+/// Sent to every subscriber after a new version of the site has been swapped in
+#[derive(Message, Clone)]
+pub struct Reloaded;
+
+/// Holds the currently served version of the site, shared between all workers.
+/// Reloading builds an entirely new site before swapping it in, so requests
+/// never observe a half-built one, and a broken build leaves the old one in place.
+pub struct SiteState {
+    current: RwLock<Arc<Site>>,
+    subscribers: Mutex<Vec<Recipient<Reloaded>>>,
+}
+
+impl SiteState {
+    pub fn new(site: Site) -> Self {
+        SiteState {
+            current: RwLock::new(Arc::new(site)),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn subscribe(&self, recipient: Recipient<Reloaded>) {
+        self.subscribers.lock().unwrap().push(recipient);
+    }
+
+    pub fn current(&self) -> Arc<Site> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    pub fn reload(&self) -> Result<(), BlogError> {
+        let site = Site::load()?;
+        *self.current.write().unwrap() = Arc::new(site);
+
+        for subscriber in self.subscribers.lock().unwrap().iter() {
+            let _ = subscriber.do_send(Reloaded);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use super::*;
+    use crate::model::{article_url, Article, Visibility};
+    use crate::search::SearchIndex;
+
+    fn article(slug: &str, language: &str) -> Article {
+        Article {
+            title: slug.into(),
+            authors: Vec::new(),
+            published: 0,
+            updated: 0,
+            body: String::new(),
+            excerpt: String::new(),
+            words: 0,
+            reading_time: 1,
+            toc: Vec::new(),
+            slug: slug.into(),
+            url: article_url(language, "en", slug),
+            aliases: Vec::new(),
+            language: language.into(),
+            translations: Vec::new(),
+            tags: Vec::new(),
+            category: None,
+            series: None,
+            related: Vec::new(),
+            visibility: Visibility::Public,
+        }
+    }
+
+    /// An English article "hello", formerly "hi", and a Danish article "hej", formerly "hejsa"
+    fn site() -> Site {
+        let mut config = Config::default();
+        config.languages.insert("da".into(), "Dansk".into());
+        config.redirects.insert("/blog/hello.html".into(), "/articles/hello".into());
+
+        let articles = vec![article("hello", "en"), article("hej", "da")];
+        let redirects: HashMap<String, usize> = vec![("hello", 0), ("hi", 0), ("hej", 1), ("hejsa", 1)].into_iter()
+            .map(|(slug, index)| (slug.to_string(), index))
+            .collect();
+
+        let world = World {
+            search: SearchIndex::new(&articles),
+            articles,
+            tags: BTreeMap::new(),
+            categories: BTreeMap::new(),
+            series: BTreeMap::new(),
+            authors: BTreeMap::new(),
+            next_scheduled: None,
+            redirects,
+        };
+
+        Site { config, tera: Tera::default(), world }
+    }
+
+    #[test]
+    fn articles_are_redirected_to_their_language() {
+        let site = site();
+
+        assert_eq!(site.redirect("/articles/hello"), None);
+        assert_eq!(site.redirect("/da/articles/hej"), None);
+        assert_eq!(site.redirect("/en/articles/hello").as_deref(), Some("/articles/hello"));
+        assert_eq!(site.redirect("/articles/hej").as_deref(), Some("/da/articles/hej"));
+        assert_eq!(site.redirect("/da/articles/hello/").as_deref(), Some("/articles/hello"));
+    }
+
+    #[test]
+    fn former_slugs_are_redirected_under_any_language() {
+        let site = site();
+
+        assert_eq!(site.redirect("/articles/hi").as_deref(), Some("/articles/hello"));
+        assert_eq!(site.redirect("/da/articles/hejsa").as_deref(), Some("/da/articles/hej"));
+        assert_eq!(site.redirect("/en/articles/HejSa").as_deref(), Some("/da/articles/hej"));
+    }
+
+    #[test]
+    fn unknown_paths_are_not_redirected() {
+        let site = site();
+
+        assert_eq!(site.redirect("/fr/articles/hello"), None);
+        assert_eq!(site.redirect("/da/posts/hej"), None);
+        assert_eq!(site.redirect("/articles/hello/comments"), None);
+        assert_eq!(site.redirect("/articles/unknown"), None);
+        assert_eq!(site.redirect("/blog/hello.html").as_deref(), Some("/articles/hello"));
+    }
+}
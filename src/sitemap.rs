@@ -0,0 +1,52 @@
+use tera::Context;
+use crate::error::BlogError;
+use crate::model::Article;
+use crate::site::Site;
+
+pub const SITEMAP_PATH: &str = "/sitemap.xml";
+
+/// Renders a sitemap of the frontpage and every listed article. Unlisted articles
+/// are left out on purpose, since search engines shouldn't go looking for them.
+pub fn render_sitemap(site: &Site) -> Result<String, BlogError> {
+    let articles: Vec<&Article> = site.world.listed();
+    let updated = articles.iter().map(|article| article.updated).max();
+
+    let mut ctx = Context::new();
+    ctx.insert("config", &site.config);
+    ctx.insert("articles", &articles);
+    ctx.insert("updated", &updated);
+
+    Ok(site.tera.render("sitemap.tera", &ctx)?)
+}
+
+/// Renders robots.txt from the crawler rules in the configuration, pointing crawlers at the sitemap
+pub fn render_robots(site: &Site) -> String {
+    let mut robots = String::new();
+
+    for rule in &site.config.robots {
+        robots.push_str(&format!("User-agent: {}\n", rule.user_agent));
+
+        for path in &rule.allow {
+            robots.push_str(&format!("Allow: {}\n", path));
+        }
+
+        // An empty Disallow allows everything, which is the
+        // conventional way of writing a group without restrictions
+        if rule.disallow.is_empty() && rule.allow.is_empty() {
+            robots.push_str("Disallow:\n");
+        }
+
+        for path in &rule.disallow {
+            robots.push_str(&format!("Disallow: {}\n", path));
+        }
+
+        if let Some(delay) = rule.crawl_delay {
+            robots.push_str(&format!("Crawl-delay: {}\n", delay));
+        }
+
+        robots.push('\n');
+    }
+
+    robots.push_str(&format!("Sitemap: {}{}\n", site.config.base_url, SITEMAP_PATH));
+    robots
+}
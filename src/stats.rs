@@ -7,8 +7,8 @@ use systemstat::{System, Platform, saturating_sub_bytes};
 use circular_queue::CircularQueue;
 use crate::error::BlogError;
 
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Entry point for our route
 pub fn system_stats(req: HttpRequest, stream: Payload, srv: Data<Addr<StatisticsServer>>) 
@@ -0,0 +1,167 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use crate::error::BlogError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// The site can't be served as it is
+    Error,
+    /// Probably a mistake, but the site can be served regardless
+    Warning,
+}
+
+/// A single problem found while loading the site, pointing at
+/// the file and line responsible for it whenever that's known
+#[derive(Debug)]
+pub struct Problem {
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn error<P: AsRef<Path>>(file: P, line: Option<usize>, message: String) -> Self {
+        Problem {
+            severity: Severity::Error,
+            file: Some(file.as_ref().to_path_buf()),
+            line,
+            message,
+        }
+    }
+
+    pub fn warning<P: AsRef<Path>>(file: P, line: Option<usize>, message: String) -> Self {
+        Problem {
+            severity: Severity::Warning,
+            ..Problem::error(file, line, message)
+        }
+    }
+}
+
+/// Errors from loading a single file already name the file in their message
+impl From<BlogError> for Problem {
+    fn from(e: BlogError) -> Self {
+        let message = match e {
+            BlogError::TemplateError(message)
+            | BlogError::MissingContent(message)
+            | BlogError::MetadataError(message)
+            | BlogError::IOError(message) => message,
+            BlogError::ValidationError(report) => report.to_string(),
+        };
+
+        // Parse errors from tera start on a line of their own
+        let message = message.trim().to_string();
+
+        Problem {
+            severity: Severity::Error,
+            file: None,
+            line: None,
+            message,
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: ", file.display(), line)?,
+            (Some(file), None) => write!(f, "{}: ", file.display())?,
+            _ => (),
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Every problem found while loading the site. Loading carries on past problems
+/// wherever it can, so a single pass reports as many of them as possible.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    pub fn add<P: Into<Problem>>(&mut self, problem: P) {
+        self.problems.push(problem.into());
+    }
+
+    pub fn errors(&self) -> usize {
+        self.problems.iter().filter(|problem| problem.severity == Severity::Error).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.problems.iter().filter(|problem| problem.severity == Severity::Warning).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors() > 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Summary line, such as "2 errors and 1 warning"
+    pub fn summary(&self) -> String {
+        let plural = |count: usize, noun: &str| match count {
+            1 => format!("1 {}", noun),
+            count => format!("{} {}s", count, noun),
+        };
+
+        format!("{} and {}", plural(self.errors(), "error"), plural(self.warnings(), "warning"))
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "found {}", self.summary())?;
+
+        for problem in &self.problems {
+            writeln!(f, "{}", problem)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problems_are_counted_by_severity() {
+        let mut report = Report::default();
+        assert!(report.is_empty());
+        assert!(!report.has_errors());
+
+        report.add(Problem::warning("a.md", Some(3), "unknown author".into()));
+        assert!(!report.is_empty());
+        assert!(!report.has_errors());
+
+        report.add(Problem::error("b.md", None, "missing title".into()));
+        report.add(BlogError::MetadataError("c.md: invalid front matter\n".into()));
+
+        assert_eq!(report.errors(), 2);
+        assert_eq!(report.warnings(), 1);
+        assert!(report.has_errors());
+        assert_eq!(report.summary(), "2 errors and 1 warning");
+    }
+
+    #[test]
+    fn problems_are_reported_in_order_with_their_location() {
+        let mut report = Report::default();
+        report.add(Problem::error("b.md", None, "missing title".into()));
+        report.add(Problem::warning("a.md", Some(3), "unknown author".into()));
+        report.add(BlogError::TemplateError("\nfailed to parse template\n".into()));
+
+        assert_eq!(report.to_string(), "found 2 errors and 1 warning\n\
+            error: b.md: missing title\n\
+            warning: a.md:3: unknown author\n\
+            error: failed to parse template\n");
+    }
+}